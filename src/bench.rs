@@ -0,0 +1,233 @@
+use super::cache::CacheKey;
+use super::common::*;
+use super::tasks::{Logger, Priority, Task, TaskResult};
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// How many times every task is run when benchmarking. The warmup runs are
+// not timed.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Benchmark {
+    pub warmup: u32,
+    pub iterations: u32,
+}
+
+impl Benchmark {
+    pub const DEFAULT: Benchmark = Benchmark {
+        warmup: 1,
+        iterations: 10,
+    };
+
+    // Wraps the tasks so that running one of them benchmarks it, and adds
+    // the results of the tasks that passed to the results.
+    pub fn wrap(
+        &self,
+        tasks: Vec<Box<dyn Task>>,
+        results: &BenchmarkResults,
+    ) -> Vec<Box<dyn Task>> {
+        tasks
+            .into_iter()
+            .map(|task| {
+                Box::new(BenchmarkTask {
+                    task,
+                    benchmark: *self,
+                    results: results.clone(),
+                }) as Box<dyn Task>
+            })
+            .collect()
+    }
+}
+
+pub type BenchmarkResults = Arc<Mutex<Vec<BenchmarkResult>>>;
+
+#[derive(PartialEq, Debug)]
+pub struct BenchmarkResult {
+    pub task_name: TaskName,
+    // The answer every iteration gave.
+    pub answer: Answer,
+    // How long each iteration took, in seconds.
+    pub timings: Vec<f64>,
+}
+
+// In seconds.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Statistics {
+    pub min: f64,
+    pub median: f64,
+    pub mean: f64,
+    // Of the sample, so zero for a single iteration.
+    pub stddev: f64,
+}
+
+impl BenchmarkResult {
+    // None when no iteration was timed.
+    pub fn statistics(&self) -> Option<Statistics> {
+        if self.timings.is_empty() {
+            return None;
+        }
+        let mut timings = self.timings.clone();
+        timings.sort_by(f64::total_cmp);
+        let count = timings.len();
+        let middle = count / 2;
+        let median = if count.is_multiple_of(2) {
+            (timings[middle - 1] + timings[middle]) / 2.0
+        } else {
+            timings[middle]
+        };
+        let mean = timings.iter().sum::<f64>() / count as f64;
+        let variance = if count > 1 {
+            let squares: f64 = timings.iter().map(|timing| (timing - mean).powi(2)).sum();
+            squares / (count - 1) as f64
+        } else {
+            0.0
+        };
+        Some(Statistics {
+            min: timings[0],
+            median,
+            mean,
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+// For example:
+// day-1: min 1.20ms, median 1.31ms, mean 1.33ms, stddev 85.2µs over 10 iterations
+// day-2: no iterations
+impl fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let statistics = match self.statistics() {
+            Some(statistics) => statistics,
+            None => return write!(f, "{}: no iterations", self.task_name),
+        };
+        write!(
+            f,
+            "{}: min {}, median {}, mean {}, stddev {} over {} iterations",
+            self.task_name,
+            format_seconds(statistics.min),
+            format_seconds(statistics.median),
+            format_seconds(statistics.mean),
+            format_seconds(statistics.stddev),
+            self.timings.len()
+        )
+    }
+}
+
+// With three significant digits, for example 12.3ms.
+pub fn format_seconds(seconds: f64) -> String {
+    let (value, unit) = if seconds >= 1.0 {
+        (seconds, "s")
+    } else if seconds >= 1e-3 {
+        (seconds * 1e3, "ms")
+    } else if seconds >= 1e-6 {
+        (seconds * 1e6, "µs")
+    } else {
+        (seconds * 1e9, "ns")
+    };
+    let decimals = if value >= 100.0 {
+        0
+    } else if value >= 10.0 {
+        1
+    } else {
+        2
+    };
+    format!("{:.*}{}", decimals, value, unit)
+}
+
+// Runs its task once for every warmup and iteration. It fails if any of them
+// fail, or if they do not all give the same answer. It is not retried, since
+// a retry would hide the failure.
+struct BenchmarkTask {
+    task: Box<dyn Task>,
+    benchmark: Benchmark,
+    results: BenchmarkResults,
+}
+
+impl Task for BenchmarkTask {
+    // The task gets the logger of the benchmark, so what it logs is kept for
+    // every run, and its progress is replaced by the progress of the
+    // benchmark after every run.
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        let Benchmark { warmup, iterations } = self.benchmark;
+        let total = warmup + iterations;
+        let mut answer = None;
+        let mut timings = Vec::new();
+        for run in 1..=total {
+            if logger.is_cancelled() {
+                return Err(format!("Cancelled after {} of {} runs", run - 1, total));
+            }
+            let started = Instant::now();
+            let result = self.task.run(logger);
+            let elapsed = started.elapsed().as_secs_f64();
+            let name = if run <= warmup {
+                format!("Warmup {} of {}", run, warmup)
+            } else {
+                format!("Iteration {} of {}", run - warmup, iterations)
+            };
+            let run_answer = result.map_err(|error| format!("{} failed: {}", name, error))?;
+            match &answer {
+                None => answer = Some(run_answer),
+                Some(first) if *first != run_answer => {
+                    return Err(format!(
+                        "{} answered {} instead of {}",
+                        name,
+                        format_answer(&run_answer),
+                        format_answer(first)
+                    ));
+                }
+                Some(_) => (),
+            }
+            if run > warmup {
+                timings.push(elapsed);
+            }
+            logger.progress(Progress::Count {
+                current: run as u64,
+                total: total as u64,
+                unit: String::from("runs"),
+            });
+        }
+        let answer = answer.unwrap_or_default();
+        let result = BenchmarkResult {
+            task_name: self.task.name(),
+            answer: answer.clone(),
+            timings,
+        };
+        logger.info(&result.to_string());
+        self.results.lock().unwrap().push(result);
+        Ok(answer)
+    }
+
+    fn name(&self) -> TaskName {
+        self.task.name()
+    }
+
+    fn priority(&self) -> Priority {
+        self.task.priority()
+    }
+
+    fn dependencies(&self) -> Vec<TaskName> {
+        self.task.dependencies()
+    }
+
+    fn tags(&self) -> Vec<String> {
+        self.task.tags()
+    }
+
+    fn inputs(&self) -> Vec<PathBuf> {
+        self.task.inputs()
+    }
+
+    // A benchmark is never taken from the cache.
+    fn cache_key(&self) -> Option<CacheKey> {
+        None
+    }
+}
+
+fn format_answer(answer: &Answer) -> String {
+    match answer {
+        Some(answer) => format!("{:?}", answer),
+        None => String::from("nothing"),
+    }
+}
@@ -0,0 +1,242 @@
+use super::common::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// What the result of a deterministic task depends on. While none of it
+// changes, the task is not run again and its result is taken from the cache.
+#[derive(Clone, Default, Debug)]
+pub struct CacheKey {
+    // Files, or directories with all the files in them, that the task reads.
+    pub files: Vec<PathBuf>,
+    pub params: Vec<(String, String)>,
+    // To change when the task itself changes in a way that changes its
+    // result.
+    pub version: String,
+    // The answers of the tasks it depends on, by name. Set by the runner.
+    pub dependency_answers: Vec<(TaskName, Answer)>,
+}
+
+impl CacheKey {
+    // A SHA-256 of the task name, the key and the content of its files, in
+    // hex. A missing answer and an empty one give different hashes.
+    pub fn hash(&self, task_name: &str) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        add_field(&mut hasher, task_name.as_bytes());
+        add_field(&mut hasher, self.version.as_bytes());
+        for (name, value) in &self.params {
+            add_field(&mut hasher, name.as_bytes());
+            add_field(&mut hasher, value.as_bytes());
+        }
+        for file in &self.files {
+            add_path(&mut hasher, file)?;
+        }
+        for (task_name, answer) in &self.dependency_answers {
+            add_field(&mut hasher, task_name.as_bytes());
+            match answer {
+                Some(answer) => add_field(&mut hasher, format!("Some {}", answer).as_bytes()),
+                None => add_field(&mut hasher, b"None"),
+            }
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+}
+
+// Every field is prefixed with its length, so that moving bytes from the end
+// of one field to the start of the next changes the hash.
+fn add_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+// The files in a directory are added in the order of their names, so that
+// the hash does not depend on the order the file system lists them in.
+// Symbolic links are not followed, so that a link to a directory above it
+// cannot make the walk go on forever. Where a link points to is added
+// instead.
+fn add_path(hasher: &mut Sha256, path: &Path) -> io::Result<()> {
+    add_field(hasher, path.to_string_lossy().as_bytes());
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if file_type.is_symlink() {
+        add_field(hasher, b"symlink");
+        add_field(hasher, fs::read_link(path)?.to_string_lossy().as_bytes());
+    } else if file_type.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        entries.sort();
+        add_field(hasher, b"directory");
+        for entry in entries {
+            add_path(hasher, &entry)?;
+        }
+    } else {
+        add_field(hasher, b"file");
+        add_field(hasher, &fs::read(path)?);
+    }
+    Ok(())
+}
+
+// The result of a task that finished, and the log it had, to show again
+// instead of running the task.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct CachedResult {
+    pub task_name: TaskName,
+    pub answer: Answer,
+    // The changes of the task and its subtasks, by subtask path, other than
+    // the status and progress of the task.
+    pub log: Vec<(Vec<TaskName>, TaskChange)>,
+    pub stored: SystemTime,
+}
+
+// Keeps the results of tasks in a directory, with a file for every cache key.
+pub struct ResultCache {
+    directory: PathBuf,
+    // The log so far of the running tasks whose result is to be cached, and
+    // their cache keys, by task name.
+    recordings: Mutex<HashMap<TaskName, Recording>>,
+}
+
+struct Recording {
+    key: String,
+    log: Vec<(Vec<TaskName>, TaskChange)>,
+}
+
+const EXTENSION: &str = "result";
+// How many results this process has started to store.
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+impl ResultCache {
+    pub fn new(directory: PathBuf) -> ResultCache {
+        ResultCache {
+            directory,
+            recordings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    // A result that cannot be read is treated as missing, so that the task
+    // is run again and the result replaced.
+    pub fn load(&self, key: &str) -> Option<CachedResult> {
+        let bytes = fs::read(self.path_of(key)).ok()?;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    pub fn store(&self, key: &str, result: &CachedResult) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let bytes = bincode::serialize(result).map_err(io::Error::other)?;
+        // Written to a file of its own first, so that a run reading the
+        // result at the same time never sees half of it. The file name is
+        // unique to the write, so that runs storing the same key at the same
+        // time do not write to the same file.
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let temporary = self
+            .directory
+            .join(format!("{}.{}-{}.tmp", key, process::id(), write));
+        fs::write(&temporary, bytes)?;
+        fs::rename(temporary, self.path_of(key))
+    }
+
+    // The cached results by key, oldest first.
+    pub fn entries(&self) -> io::Result<Vec<(String, CachedResult)>> {
+        let mut entries = Vec::new();
+        for key in self.keys()? {
+            if let Some(result) = self.load(&key) {
+                entries.push((key, result));
+            }
+        }
+        entries.sort_by_key(|(_, result)| result.stored);
+        Ok(entries)
+    }
+
+    // Removes every cached result, and returns how many there were.
+    pub fn clear(&self) -> io::Result<usize> {
+        let keys = self.keys()?;
+        for key in &keys {
+            fs::remove_file(self.path_of(key))?;
+        }
+        Ok(keys.len())
+    }
+
+    fn keys(&self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                if let Some(key) = path.file_stem() {
+                    keys.push(key.to_string_lossy().into_owned());
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    fn path_of(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.{}", key, EXTENSION))
+    }
+
+    // Called before a task with a cache key is run, to keep its log until
+    // it is done.
+    pub(crate) fn start_recording(&self, task_name: TaskName, key: String) {
+        let recording = Recording {
+            key,
+            log: Vec::new(),
+        };
+        self.recordings.lock().unwrap().insert(task_name, recording);
+    }
+
+    // Called with every change of every task. The result is stored once the
+    // task has finished, and dropped if it fails. Only the log of the last
+    // attempt of a task is kept.
+    pub(crate) fn record(&self, task_name: &str, subtask_path: &[TaskName], change: &TaskChange) {
+        let mut recordings = self.recordings.lock().unwrap();
+        let recording = match recordings.get_mut(task_name) {
+            Some(recording) => recording,
+            None => return,
+        };
+        match change {
+            TaskChange::TaskStatus(status) if subtask_path.is_empty() => match status {
+                Status::Finished(answer) => {
+                    let recording = recordings.remove(task_name).unwrap();
+                    let result = CachedResult {
+                        task_name: String::from(task_name),
+                        answer: answer.clone(),
+                        log: recording.log,
+                        stored: SystemTime::now(),
+                    };
+                    if let Err(error) = self.store(&recording.key, &result) {
+                        log::warn!("Could not cache the result of {}: {}", task_name, error);
+                    }
+                }
+                Status::Failed(_) | Status::Skipped => {
+                    recordings.remove(task_name);
+                }
+                Status::Pending | Status::Running => (),
+            },
+            TaskChange::TaskAttempt(_) if subtask_path.is_empty() => recording.log.clear(),
+            TaskChange::TaskProgress(_) | TaskChange::TaskCached | TaskChange::TaskFlaky => (),
+            _ => recording.log.push((subtask_path.to_vec(), change.clone())),
+        }
+    }
+}
@@ -0,0 +1,689 @@
+use super::bench::{Benchmark, BenchmarkResult, BenchmarkResults};
+use super::cache::ResultCache;
+use super::common::*;
+use super::history::{self, History, HistoryRecorder, RunRecord};
+use super::log_bridge::LogBridge;
+use super::recording::{self, RecordedEvent, Recorder};
+use super::reports::{JsonReport, JunitReport, PlainReport};
+use super::selection::{Pattern, Selection};
+use super::summary::{ExitPolicy, RunSummary, EXIT_FAILED, EXIT_SUCCESS};
+use super::task_file;
+use super::tasks::{Task, TaskRunner};
+use super::view::{Console, Timestamps};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// Like EX_USAGE of sysexits.h.
+pub const EXIT_USAGE: i32 = 64;
+// How long the inputs have to stay unchanged before the tasks are run again.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+const DEFAULT_CACHE_DIR: &str = ".console-runner/cache";
+const DEFAULT_HISTORY: &str = ".console-runner/history.jsonl";
+
+pub const USAGE: &str = "Usage: console-runner [OPTIONS] [TASK_FILE]
+
+Runs the tasks in TASK_FILE, a TOML file with a [[task]] table for every
+task, like:
+
+  [[task]]
+  name = \"test\"
+  command = \"cargo\"
+  args = [\"test\"]
+  depends_on = [\"build\"]
+
+A task can also have env, working_dir, timeout, retries, tags, inputs and a
+cache key like cache = { files = [\"src\"], version = \"1\" }. Runs a demo
+without a task file.
+
+Options:
+  -j, --jobs <N>          Run N tasks at the same time [default: number of CPUs]
+  -o, --output <OUTPUT>   How to show the run: console, plain, json or junit
+                          [default: console]
+      --log-level <LEVEL>
+                          Only show what is logged at LEVEL or above: trace,
+                          debug, info, warn or error [default: info]
+      --timestamps <WHEN>
+                          Stamp what the tasks print in the console with the
+                          time since the run started or the time of day:
+                          relative or absolute
+  -f, --filter <PATTERN>  Only run the tasks that match PATTERN, the tasks that
+                          depend on them, and the tasks that all of those
+                          depend on. PATTERN is a glob of the name like
+                          build-*, a regex of the name like re:^build or a tag
+                          like tag:ci. Can be given more than once
+      --exclude <PATTERN> Do not run the tasks that match PATTERN
+      --show-excluded     Show the tasks that are not run as skipped
+      --fail-fast         Skip the remaining tasks once a task has failed
+      --timeout <TIME>    Stop the run after TIME, like 90, 1.5s, 500ms or 10m
+      --dry-run           Show the order the tasks would run in, without
+                          running them
+      --dot               Print the dependency graph of the tasks in the DOT
+                          language of Graphviz, without running them
+  -w, --watch             Keep running, and run the tasks again when their
+                          inputs change, until stopped with Ctrl-C
+      --cache-dir <DIR>   Keep the results of the tasks with a cache key in DIR
+                          [default: .console-runner/cache]
+      --no-cache          Run every task, without using or keeping results
+      --show-cache        List the cached results
+      --clear-cache       Remove the cached results
+      --record <FILE>     Record what is shown of the run to FILE
+      --replay <FILE>     Show the run recorded in FILE instead of running tasks
+      --speed <N>         Replay N times faster, or as fast as possible with max
+                          [default: 1]
+      --history <FILE>    Add every run to the history in FILE, and show how
+                          long the tasks took the last time
+                          [default: .console-runner/history.jsonl]
+      --no-history        Neither use nor add to the history
+      --repeat <N>        Run the tasks N times without the cache, and show how
+                          often each of them passed, to find flaky tasks
+      --bench             Time the tasks, running one at a time, and show the
+                          min, median, mean and standard deviation. A task
+                          fails if its runs do not all give the same answer
+      --warmup <N>        Run every task N times before timing it, for --bench
+                          [default: 1]
+      --iterations <N>    Time every task N times, for --bench [default: 10]
+      --regressions <N>   List the tasks that became slower or started to fail
+                          in the last run, compared to the N runs before it.
+                          Runs that were interrupted or timed out are left out
+      --strict            Also exit with an error if a task gave no answer
+      --lenient           Always exit with 0
+  -h, --help              Show this help";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Output {
+    Console,
+    Plain,
+    Json,
+    Junit,
+}
+
+// Run instead of the tasks.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CacheCommand {
+    Show,
+    Clear,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Options {
+    pub task_file: Option<PathBuf>,
+    pub jobs: Option<u16>,
+    pub output: Output,
+    pub timestamps: Timestamps,
+    pub log_level: Level,
+    pub selection: Selection,
+    pub fail_fast: bool,
+    pub timeout: Option<Duration>,
+    pub exit_policy: ExitPolicy,
+    pub dry_run: bool,
+    pub dot: bool,
+    pub watch: bool,
+    // None when nothing is to be cached.
+    pub cache_dir: Option<PathBuf>,
+    pub cache_command: Option<CacheCommand>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub speed: f64,
+    // None when the runs are not kept.
+    pub history: Option<PathBuf>,
+    pub repeat: u32,
+    // Set when benchmarking.
+    pub bench: Option<Benchmark>,
+    // The number of earlier runs to compare the last run with.
+    pub regressions: Option<usize>,
+    pub help: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            task_file: None,
+            jobs: None,
+            output: Output::Console,
+            timestamps: Timestamps::Hidden,
+            log_level: Level::Info,
+            selection: Selection::ALL,
+            fail_fast: false,
+            timeout: None,
+            exit_policy: ExitPolicy::Normal,
+            dry_run: false,
+            dot: false,
+            watch: false,
+            cache_dir: Some(PathBuf::from(DEFAULT_CACHE_DIR)),
+            cache_command: None,
+            record: None,
+            replay: None,
+            speed: 1.0,
+            history: Some(PathBuf::from(DEFAULT_HISTORY)),
+            repeat: 1,
+            bench: None,
+            regressions: None,
+            help: false,
+        }
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::new();
+    // The options of a benchmark only count with --bench, wherever it is.
+    let mut benchmark = Benchmark::DEFAULT;
+    let mut benchmark_option = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-j" | "--jobs" => {
+                let jobs = value()?;
+                options.jobs = match jobs.parse() {
+                    Ok(0) | Err(_) => return Err(format!("Invalid number of jobs: {}", jobs)),
+                    Ok(jobs) => Some(jobs),
+                };
+            }
+            "-o" | "--output" => {
+                options.output = match value()?.as_str() {
+                    "console" => Output::Console,
+                    "plain" => Output::Plain,
+                    "json" => Output::Json,
+                    "junit" => Output::Junit,
+                    output => return Err(format!("Unknown output: {}", output)),
+                }
+            }
+            "--log-level" => {
+                options.log_level = match value()?.as_str() {
+                    "trace" => Level::Trace,
+                    "debug" => Level::Debug,
+                    "info" => Level::Info,
+                    "warn" => Level::Warn,
+                    "error" => Level::Error,
+                    level => return Err(format!("Unknown log level: {}", level)),
+                }
+            }
+            "--timestamps" => {
+                options.timestamps = match value()?.as_str() {
+                    "relative" => Timestamps::Relative,
+                    "absolute" => Timestamps::Absolute,
+                    timestamps => return Err(format!("Unknown timestamps: {}", timestamps)),
+                }
+            }
+            "-f" | "--filter" => options.selection.include.push(Pattern::parse(value()?)?),
+            "--exclude" => options.selection.exclude.push(Pattern::parse(value()?)?),
+            "--show-excluded" => options.selection.show_excluded = true,
+            "--fail-fast" => options.fail_fast = true,
+            "--timeout" => options.timeout = Some(parse_duration(value()?)?),
+            "--strict" => options.exit_policy = ExitPolicy::Strict,
+            "--lenient" => options.exit_policy = ExitPolicy::Lenient,
+            "--dry-run" => options.dry_run = true,
+            "--dot" => options.dot = true,
+            "-w" | "--watch" => options.watch = true,
+            "--cache-dir" => options.cache_dir = Some(PathBuf::from(value()?)),
+            "--no-cache" => options.cache_dir = None,
+            "--show-cache" => options.cache_command = Some(CacheCommand::Show),
+            "--clear-cache" => options.cache_command = Some(CacheCommand::Clear),
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--replay" => options.replay = Some(PathBuf::from(value()?)),
+            "--speed" => {
+                let speed = value()?;
+                options.speed = match speed.as_str() {
+                    "max" => f64::INFINITY,
+                    _ => match speed.parse::<f64>() {
+                        Ok(speed) if speed > 0.0 => speed,
+                        _ => return Err(format!("Invalid speed: {}", speed)),
+                    },
+                };
+            }
+            "--history" => options.history = Some(PathBuf::from(value()?)),
+            "--no-history" => options.history = None,
+            "--repeat" => {
+                let repeat = value()?;
+                options.repeat = match repeat.parse() {
+                    Ok(0) | Err(_) => return Err(format!("Invalid number of repeats: {}", repeat)),
+                    Ok(repeat) => repeat,
+                };
+            }
+            "--bench" => options.bench = Some(Benchmark::DEFAULT),
+            "--warmup" => {
+                let warmup = value()?;
+                benchmark.warmup = match warmup.parse() {
+                    Ok(warmup) => warmup,
+                    Err(_) => return Err(format!("Invalid number of warmup runs: {}", warmup)),
+                };
+                benchmark_option = Some(arg);
+            }
+            "--iterations" => {
+                let iterations = value()?;
+                benchmark.iterations = match iterations.parse() {
+                    Ok(0) | Err(_) => {
+                        return Err(format!("Invalid number of iterations: {}", iterations))
+                    }
+                    Ok(iterations) => iterations,
+                };
+                benchmark_option = Some(arg);
+            }
+            "--regressions" => {
+                let runs = value()?;
+                options.regressions = match runs.parse() {
+                    Ok(0) | Err(_) => return Err(format!("Invalid number of runs: {}", runs)),
+                    Ok(runs) => Some(runs),
+                };
+            }
+            "-h" | "--help" => options.help = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ if options.task_file.is_some() => {
+                return Err(format!("Only one task file can be given, got {}", arg))
+            }
+            _ => options.task_file = Some(PathBuf::from(arg)),
+        }
+    }
+    if options.watch && options.repeat > 1 {
+        return Err(String::from("--repeat cannot be used with --watch"));
+    }
+    // A JUnit report is a single document for a single run.
+    if options.output == Output::Junit && options.repeat > 1 {
+        return Err(String::from("--repeat cannot be used with -o junit"));
+    }
+    match (&options.bench, benchmark_option) {
+        (Some(_), _) => options.bench = Some(benchmark),
+        (None, Some(option)) => return Err(format!("{} needs --bench", option)),
+        (None, None) => (),
+    }
+    if options.bench.is_some() && (options.watch || options.repeat > 1) {
+        return Err(String::from(
+            "--bench cannot be used with --watch or --repeat",
+        ));
+    }
+    // A benchmark runs one task at a time.
+    if options.bench.is_some() && options.jobs.is_some() {
+        return Err(String::from("--jobs cannot be used with --bench"));
+    }
+    Ok(options)
+}
+
+// Seconds, unless followed by one of the units ms, s, m or h.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+    let split = text
+        .find(|character: char| !character.is_ascii_digit() && character != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let scale = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        _ => return Err(format!("Invalid time: {}", text)),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * scale).ok())
+        .ok_or_else(|| format!("Invalid time: {}", text))
+}
+
+// Runs the binary with the arguments after the program name, and returns the
+// exit code.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return EXIT_SUCCESS;
+    }
+    if let Some(cache_command) = options.cache_command {
+        let cache_dir = options
+            .cache_dir
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR));
+        return run_cache_command(cache_command, &ResultCache::new(cache_dir));
+    }
+    if let Some(compared_runs) = options.regressions {
+        let path = options
+            .history
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_HISTORY));
+        return report_regressions(&History::new(path), compared_runs);
+    }
+    if let Some(path) = &options.replay {
+        return replay(path, options.speed, options.output, options.timestamps);
+    }
+    let mut tasks = match &options.task_file {
+        Some(path) => match task_file::load(path) {
+            Ok(tasks) => tasks,
+            Err(error) => {
+                eprintln!("{}", error);
+                return EXIT_USAGE;
+            }
+        },
+        None => super::demo_tasks(),
+    };
+    let (output, timestamps) = (options.output, options.timestamps);
+    let (exit_policy, log_level) = (options.exit_policy, options.log_level);
+    let (dry_run, dot, watch) = (options.dry_run, options.dot, options.watch);
+    let (repeat, benchmark) = (options.repeat as usize, options.bench);
+    let (task_file, record) = (options.task_file.clone(), options.record.clone());
+    // The runs of a benchmark would spoil the durations in the history.
+    let history = match benchmark {
+        Some(_) => None,
+        None => options.history.clone().map(History::new),
+    };
+    let task_runner = create_task_runner(options);
+    if dot {
+        print!("{}", task_runner.plan(tasks, &mut NoView {}).to_dot());
+        return EXIT_SUCCESS;
+    }
+    let previous_durations = match &history {
+        Some(history) => history.previous_durations().unwrap_or_else(|error| {
+            eprintln!("Could not read {}: {}", history.path().display(), error);
+            HashMap::new()
+        }),
+        None => HashMap::new(),
+    };
+    let mut view = create_view(output, timestamps, previous_durations);
+    if dry_run {
+        task_runner.plan(tasks, view.as_mut());
+        return EXIT_SUCCESS;
+    }
+    if let Some(path) = record {
+        view = match Recorder::create(&path, view) {
+            Ok(recorder) => Box::new(recorder),
+            Err(error) => {
+                eprintln!("Could not record to {}: {}", path.display(), error);
+                return EXIT_USAGE;
+            }
+        };
+    }
+    if let Some(history) = history {
+        let fingerprints = history::fingerprints(&tasks);
+        view = Box::new(HistoryRecorder::new(history, fingerprints, view));
+    }
+    let benchmark_results = BenchmarkResults::default();
+    if let Some(benchmark) = benchmark {
+        tasks = benchmark.wrap(tasks, &benchmark_results);
+    }
+    // Another logger may have been set already, for example by a program that
+    // runs the command line of the runner. What is logged then goes there.
+    let _ = LogBridge::install(to_level_filter(log_level));
+    // The task file is read again for every round, so that changes to the
+    // commands are picked up. While it has an error, which is common while it
+    // is being edited, the last version that loaded is used.
+    let last_loaded = RefCell::new(None);
+    let create_tasks = || match &task_file {
+        Some(path) => load_task_file(path, &last_loaded),
+        None => super::demo_tasks(),
+    };
+    let summaries = if watch {
+        match task_runner.watch(
+            &create_tasks,
+            task_file.as_deref(),
+            WATCH_DEBOUNCE,
+            view.as_mut(),
+        ) {
+            Ok(summary) => vec![summary],
+            Err(error) => {
+                eprintln!("{}", error);
+                return EXIT_USAGE;
+            }
+        }
+    } else {
+        let mut summaries = vec![task_runner.run(tasks, view.as_mut())];
+        while summaries.len() < repeat && !summaries[summaries.len() - 1].interrupted {
+            summaries.push(task_runner.run(create_tasks(), view.as_mut()));
+        }
+        summaries
+    };
+    let summary = &summaries[summaries.len() - 1];
+    if matches!(output, Output::Console | Output::Plain) {
+        println!("{}", summary);
+    }
+    if repeat > 1 {
+        print_outcomes(&summaries, output);
+    }
+    if benchmark.is_some() {
+        let results = benchmark_results.lock().unwrap();
+        print_benchmark_results(&results, summary, output);
+    }
+    // A repeat fails if any of the runs failed.
+    summaries
+        .iter()
+        .map(|summary| summary.exit_code(exit_policy))
+        .find(|exit_code| *exit_code != EXIT_SUCCESS)
+        .unwrap_or(EXIT_SUCCESS)
+}
+
+fn load_task_file(path: &Path, last_loaded: &RefCell<Option<String>>) -> Vec<Box<dyn Task>> {
+    let loaded = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))
+        .and_then(|content| {
+            let tasks = task_file::parse(&content, &path.display().to_string())?;
+            *last_loaded.borrow_mut() = Some(content);
+            Ok(tasks)
+        });
+    match (loaded, &*last_loaded.borrow()) {
+        (Ok(tasks), _) => tasks,
+        (Err(error), Some(content)) => {
+            log::error!("Keeping the tasks as they were: {}", error);
+            task_file::parse(content, &path.display().to_string()).unwrap_or_default()
+        }
+        (Err(error), None) => {
+            log::error!("{}", error);
+            Vec::new()
+        }
+    }
+}
+
+// How often each task passed in the runs of a repeat.
+fn print_outcomes(summaries: &[RunSummary], output: Output) {
+    let runs: Vec<RunRecord> = summaries
+        .iter()
+        .map(|summary| RunRecord::new(summary, SystemTime::now()))
+        .collect();
+    let outcomes = history::count_outcomes(&runs);
+    match output {
+        Output::Console | Output::Plain => {
+            for flakiness in &outcomes {
+                println!("{}", flakiness);
+            }
+        }
+        Output::Json => println!("{}", serde_json::json!({ "outcomes": outcomes })),
+        Output::Junit => (),
+    }
+}
+
+// In the order of the tasks in the summary.
+fn print_benchmark_results(results: &[BenchmarkResult], summary: &RunSummary, output: Output) {
+    let mut results: Vec<&BenchmarkResult> = results.iter().collect();
+    results.sort_by_key(|result| {
+        let position = summary
+            .tasks
+            .iter()
+            .position(|(name, _)| *name == result.task_name);
+        position.unwrap_or(usize::MAX)
+    });
+    match output {
+        Output::Console | Output::Plain => {
+            for result in results {
+                println!("{}", result);
+            }
+        }
+        Output::Json => {
+            let benchmarks: Vec<serde_json::Value> = results
+                .iter()
+                .map(|result| {
+                    serde_json::json!({
+                        "task_name": result.task_name,
+                        "answer": result.answer,
+                        "iterations": result.timings.len(),
+                        "statistics": result.statistics(),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::json!({ "benchmarks": benchmarks }));
+        }
+        Output::Junit => (),
+    }
+}
+
+fn create_task_runner(options: Options) -> TaskRunner {
+    let default_jobs = thread::available_parallelism().map_or(2, |jobs| jobs.get() as u16);
+    TaskRunner {
+        // Tasks that run at the same time would slow each other down.
+        thread_count: match options.bench {
+            Some(_) => 1,
+            None => options.jobs.unwrap_or(default_jobs),
+        },
+        view_update_period: 100,
+        timeout: options.timeout,
+        fail_fast: options.fail_fast,
+        log_level: options.log_level,
+        grace_period: Duration::from_secs(5),
+        selection: options.selection,
+        // Results taken from the cache would hide how often the tasks fail,
+        // and how long they take.
+        cache_dir: if options.repeat == 1 && options.bench.is_none() {
+            options.cache_dir
+        } else {
+            None
+        },
+    }
+}
+
+fn to_level_filter(level: Level) -> log::LevelFilter {
+    match level {
+        Level::Trace => log::LevelFilter::Trace,
+        Level::Debug => log::LevelFilter::Debug,
+        Level::Info => log::LevelFilter::Info,
+        Level::Warn => log::LevelFilter::Warn,
+        Level::Error => log::LevelFilter::Error,
+    }
+}
+
+// The summary of the recorded run is shown like after a run, but the exit
+// code only tells if the replay worked.
+fn replay(path: &Path, speed: f64, output: Output, timestamps: Timestamps) -> i32 {
+    let events = match recording::load(path) {
+        Ok(events) => events,
+        Err(error) => {
+            eprintln!("Could not replay {}: {}", path.display(), error);
+            return EXIT_USAGE;
+        }
+    };
+    if !matches!(events.last(), Some((_, RecordedEvent::Finish(_)))) {
+        eprintln!(
+            "The recording of {} ends before the run did",
+            path.display()
+        );
+    }
+    let mut view = create_view(output, timestamps, HashMap::new());
+    let summary = recording::replay(events, speed, view.as_mut());
+    if let (Some(summary), Output::Console | Output::Plain) = (summary, output) {
+        println!("{}", summary);
+    }
+    EXIT_SUCCESS
+}
+
+fn run_cache_command(cache_command: CacheCommand, cache: &ResultCache) -> i32 {
+    let directory = cache.directory().display();
+    let done = match cache_command {
+        CacheCommand::Show => cache.entries().map(|entries| {
+            let now = SystemTime::now();
+            for (key, result) in &entries {
+                let age = now.duration_since(result.stored).unwrap_or_default();
+                let answer = match &result.answer {
+                    Some(answer) => format!(": {}", answer),
+                    None => String::new(),
+                };
+                let key = key.get(..12).unwrap_or(key);
+                println!(
+                    "{}  {:>4} ago  {}{}",
+                    key,
+                    format_age(age),
+                    result.task_name,
+                    answer
+                );
+            }
+            println!("{} cached results in {}", entries.len(), directory);
+        }),
+        CacheCommand::Clear => cache
+            .clear()
+            .map(|removed| println!("Removed {} cached results from {}", removed, directory)),
+    };
+    match done {
+        Ok(()) => EXIT_SUCCESS,
+        Err(error) => {
+            eprintln!("Could not use the cache in {}: {}", directory, error);
+            EXIT_FAILED
+        }
+    }
+}
+
+// Exits with an error if there are any, so that a CI job can fail on them.
+fn report_regressions(history: &History, compared_runs: usize) -> i32 {
+    let runs = match history.runs() {
+        Ok(runs) => runs,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", history.path().display(), error);
+            return EXIT_USAGE;
+        }
+    };
+    let regressions = history::find_regressions(&runs, compared_runs);
+    for regression in &regressions {
+        println!("{}", regression);
+    }
+    let complete_runs = runs.iter().filter(|run| run.is_complete()).count();
+    let compared_runs = compared_runs.min(complete_runs.saturating_sub(1));
+    println!(
+        "{} regressions in the last run, compared to the {} runs before it",
+        regressions.len(),
+        compared_runs
+    );
+    if regressions.is_empty() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILED
+    }
+}
+
+// For example 45s, 12m or 3d.
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+fn create_view(
+    output: Output,
+    timestamps: Timestamps,
+    previous_durations: HashMap<TaskName, Duration>,
+) -> Box<dyn View> {
+    match output {
+        Output::Console => {
+            let mut console = Console::with_timestamps(timestamps);
+            console.set_previous_durations(previous_durations);
+            Box::new(console)
+        }
+        Output::Plain => Box::new(PlainReport::new()),
+        Output::Json => Box::new(JsonReport::new()),
+        Output::Junit => Box::new(JunitReport::new()),
+    }
+}
+
+// For when only the plan is printed.
+struct NoView {}
+
+impl View for NoView {
+    fn initialize(&mut self, _: Vec<TaskName>) {}
+
+    fn update(&mut self, _: TaskUpdate, _: SystemTime) {}
+}
@@ -0,0 +1,259 @@
+use super::cache::CacheKey;
+use super::common::*;
+use super::tasks::{kill_process_group, Logger, RetryPolicy, Task, TaskResult};
+use regex::Regex;
+use signal_hook::consts::{SIGKILL, SIGTERM};
+use signal_hook::low_level::signal_name;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often a running command checks if the run has been cancelled.
+const CANCEL_CHECK_PERIOD: Duration = Duration::from_millis(50);
+
+// What a command that exits successfully gives as its answer.
+pub enum CommandAnswer {
+    Nothing,
+    // The last line the command printed to stdout.
+    LastLine,
+    // The first match of the regex in the stdout of the command, or the
+    // first capture group if the regex has one.
+    Match(Regex),
+}
+
+// Runs an external command as a task. The stdout and stderr of the command
+// are passed on to the log of the task line by line, and the task fails if
+// the command exits with anything but 0.
+//
+// The command is run in a process group of its own, so that the whole group
+// can be stopped if the run is cancelled, including what the command started.
+pub struct CommandTask {
+    pub name: TaskName,
+    // Spawning a command needs it to be mutable.
+    command: Mutex<Command>,
+    pub answer: CommandAnswer,
+    pub retry_policy: RetryPolicy,
+    // The command is sent SIGTERM if it runs for longer than this, and
+    // SIGKILL if it is still running the grace period of the runner after
+    // that.
+    pub timeout: Option<Duration>,
+    pub dependencies: Vec<TaskName>,
+    pub tags: Vec<String>,
+    pub inputs: Vec<PathBuf>,
+    // The command line and environment are added to the key, so that
+    // changing the command does not give the result of the old one.
+    pub cache_key: Option<CacheKey>,
+}
+
+enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+impl CommandTask {
+    pub fn new(name: &str, command: Command) -> CommandTask {
+        CommandTask::with_answer(name, command, CommandAnswer::Nothing)
+    }
+
+    pub fn with_answer(name: &str, command: Command, answer: CommandAnswer) -> CommandTask {
+        CommandTask {
+            name: String::from(name),
+            command: Mutex::new(command),
+            answer,
+            retry_policy: RetryPolicy::default(),
+            timeout: None,
+            dependencies: Vec::new(),
+            tags: Vec::new(),
+            inputs: Vec::new(),
+            cache_key: None,
+        }
+    }
+
+    // The command, locked while it is used.
+    pub fn command(&self) -> MutexGuard<'_, Command> {
+        self.command.lock().unwrap()
+    }
+
+    fn find_answer(&self, stdout: &[String]) -> Answer {
+        match &self.answer {
+            CommandAnswer::Nothing => None,
+            CommandAnswer::LastLine => stdout.last().cloned(),
+            CommandAnswer::Match(regex) => stdout.iter().find_map(|line| {
+                let captures = regex.captures(line)?;
+                let found = captures.get(1).or_else(|| captures.get(0))?;
+                Some(String::from(found.as_str()))
+            }),
+        }
+    }
+}
+
+impl Task for CommandTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        let mut child = self
+            .command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|error| format!("Could not start the command: {}", error))?;
+        let process_group = child.id();
+        logger.cancellation().add_process_group(process_group);
+        // The streams are read on threads of their own so that neither of
+        // them can fill up and block the command.
+        let (sender, receiver) = channel();
+        read_lines(child.stdout.take().unwrap(), sender.clone(), OutputLine::Stdout);
+        read_lines(child.stderr.take().unwrap(), sender, OutputLine::Stderr);
+        let mut stdout = Vec::new();
+        let mut output_open = true;
+        let mut terminated = false;
+        let mut killed = false;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        // Once the command has closed its output, which it can do before it
+        // exits, the run keeps checking on it the same way until it exits.
+        let exit_status = loop {
+            if output_open {
+                match receiver.recv_timeout(CANCEL_CHECK_PERIOD) {
+                    Ok(OutputLine::Stdout(line)) => {
+                        logger.log(&line);
+                        stdout.push(line);
+                    }
+                    Ok(OutputLine::Stderr(line)) => logger.log_stderr(&line),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => output_open = false,
+                }
+            } else {
+                match child.try_wait() {
+                    Ok(Some(exit_status)) => break Ok(exit_status),
+                    Ok(None) => thread::sleep(CANCEL_CHECK_PERIOD),
+                    Err(error) => break Err(error),
+                }
+            }
+            // The runner sends SIGKILL if the group has not stopped when the
+            // grace period is over.
+            let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if (logger.is_cancelled() || timed_out) && !terminated {
+                kill_process_group(process_group, SIGTERM);
+                terminated = true;
+            }
+            let given_up = deadline.is_some_and(|deadline| {
+                Instant::now() >= deadline + logger.cancellation().grace_period()
+            });
+            if given_up && !killed {
+                kill_process_group(process_group, SIGKILL);
+                killed = true;
+            }
+        };
+        logger.cancellation().remove_process_group(process_group);
+        let exit_status =
+            exit_status.map_err(|error| format!("Could not wait for the command: {}", error))?;
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        match (exit_status.success(), self.timeout) {
+            (true, _) => Ok(self.find_answer(&stdout)),
+            (false, Some(timeout)) if timed_out => Err(format!(
+                "Timed out after {:?}: {}",
+                timeout,
+                describe_failure(exit_status)
+            )),
+            (false, _) => Err(describe_failure(exit_status)),
+        }
+    }
+
+    fn name(&self) -> TaskName {
+        self.name.clone()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.clone()
+    }
+
+    fn dependencies(&self) -> Vec<TaskName> {
+        self.dependencies.clone()
+    }
+
+    fn tags(&self) -> Vec<String> {
+        self.tags.clone()
+    }
+
+    fn inputs(&self) -> Vec<PathBuf> {
+        self.inputs.clone()
+    }
+
+    fn cache_key(&self) -> Option<CacheKey> {
+        let mut cache_key = self.cache_key.clone()?;
+        let command = describe_command(&self.command());
+        cache_key.params.push((String::from("command"), command));
+        Some(cache_key)
+    }
+}
+
+fn read_lines(
+    stream: impl Read + Send + 'static,
+    sender: Sender<OutputLine>,
+    to_output_line: fn(String) -> OutputLine,
+) {
+    // Lines that are not valid UTF-8 are converted lossily, and the stream
+    // is read to the end even when nobody is listening anymore, so that the
+    // command never blocks on a full pipe.
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+            if buffer.ends_with(b"\n") {
+                buffer.pop();
+                if buffer.ends_with(b"\r") {
+                    buffer.pop();
+                }
+            }
+            let line = String::from_utf8_lossy(&buffer).into_owned();
+            let _ = sender.send(to_output_line(line));
+        }
+    });
+}
+
+// The program, arguments, environment and working directory, in an order
+// that does not change between runs.
+fn describe_command(command: &Command) -> String {
+    let mut parts = vec![command.get_program().to_string_lossy().into_owned()];
+    parts.extend(
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned()),
+    );
+    let mut envs: Vec<String> = command
+        .get_envs()
+        .map(|(key, value)| match value {
+            Some(value) => format!("{}={}", key.to_string_lossy(), value.to_string_lossy()),
+            None => format!("-{}", key.to_string_lossy()),
+        })
+        .collect();
+    envs.sort();
+    parts.extend(envs);
+    if let Some(dir) = command.get_current_dir() {
+        parts.push(format!("in {}", dir.display()));
+    }
+    format!("{:?}", parts)
+}
+
+fn describe_failure(exit_status: ExitStatus) -> Error {
+    match (exit_status.code(), exit_status.signal()) {
+        (Some(code), _) => format!("Exited with code {}", code),
+        (None, Some(signal)) => match signal_name(signal) {
+            Some(name) => format!("Killed by {}", name),
+            None => format!("Killed by signal {}", signal),
+        },
+        (None, None) => format!("Exited with {}", exit_status),
+    }
+}
@@ -1,31 +1,146 @@
+use super::plan::Plan;
+use super::summary::RunSummary;
 use serde::{Serialize, Deserialize};
+use std::time::SystemTime;
 
 pub trait View {
     fn initialize(&mut self, tasks: Vec<TaskName>);
-    fn update(&mut self, task_update: TaskUpdate);
+    // The time is when the change happened in the task.
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime);
+    // Called when a running task adds a task to the run. The parent is set
+    // when the task was added as a child of the task adding it. Views that
+    // only show the tasks they see updates for can ignore it.
+    fn add_task(&mut self, _task: TaskName, _parent: Option<TaskName>) {}
+    // Called when a task is no longer part of the run, for example when it
+    // was removed from the task file while watching.
+    fn remove_task(&mut self, _task: TaskName) {}
+    // Called with what was logged through the log crate outside of the
+    // tasks, for example by the code that started the run.
+    fn runner_record(&mut self, _record: LogRecord, _time: SystemTime) {}
+
+    // Called regularly while the tasks are running, for views that animate.
+    fn refresh(&mut self, _now: SystemTime) {}
+
+    // Called once the run is over, for views that report on the whole run.
+    fn finish(&mut self, _summary: &RunSummary) {}
+
+    // Called instead of running the tasks, on a dry run.
+    fn plan(&mut self, _plan: &Plan) {}
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct TaskUpdate {
     pub task_name: TaskName,
+    // The subtasks, from the outermost one, that the change is about. Empty
+    // when the change is about the task itself.
+    pub subtask_path: Vec<TaskName>,
     pub change: TaskChange
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum TaskChange {
     TaskStatus(Status),
-    TaskMessage(LogMessage)
+    TaskMessage(LogMessage),
+    TaskAttempt(Attempt),
+    TaskProgress(Progress),
+    TaskRecord(LogRecord),
+    // What the task wrote to stderr, kept apart from the rest of its log:
+    // the stderr of commands, and what tasks log with Logger::log_stderr.
+    // Known limitation: what a task prints with eprintln! is not part of it
+    // but of its TaskMessages, since the std output capture has a single
+    // buffer for println! and eprintln!.
+    TaskStderr(LogMessage),
+    // Sent before the status of a task whose result was taken from the
+    // cache instead of running it.
+    TaskCached,
+    // Sent before the status of a task that both failed and passed with the
+    // same inputs, in this run or in earlier ones.
+    TaskFlaky
+}
+
+// A leveled log message, with optional key/value fields that are kept apart
+// from the message for views that want to process them.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub message: String,
+    pub fields: Vec<(String, String)>
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+// Sent when a failed task is run again.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Attempt {
+    pub number: u32,
+    pub max_attempts: u32
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum Progress {
+    // How much of the task is done, from 0.0 to 1.0.
+    Fraction(f64),
+    // How many units of the total are done, for example bytes of a download.
+    Count {
+        current: u64,
+        total: u64,
+        unit: String
+    }
+}
+
+impl Progress {
+    pub fn done(&self) -> f64 {
+        match self {
+            Progress::Fraction(fraction) => *fraction,
+            Progress::Count { current, .. } => *current as f64,
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        match self {
+            Progress::Fraction(_) => 1.0,
+            Progress::Count { total, .. } => *total as f64,
+        }
+    }
+
+    pub fn fraction(&self) -> f64 {
+        if self.total() > 0.0 {
+            (self.done() / self.total()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
 }
 
 pub type TaskName = String;
 pub type LogMessage = String;
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 pub enum Status {
     Pending,
     Running,
     Finished(Answer),
-    Failed(Error)
+    Failed(Error),
+    Skipped
 }
 
 pub type Answer = Option<String>;
@@ -0,0 +1,494 @@
+use super::cache::CacheKey;
+use super::common::*;
+use super::summary::RunSummary;
+use super::tasks::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// A task counts as slower when it takes this many times its usual duration.
+const SLOWER_FACTOR: f64 = 1.25;
+// Differences smaller than this are noise, no matter the factor.
+const MIN_SLOWDOWN: Duration = Duration::from_millis(100);
+// How many earlier runs are looked at to tell if a task is flaky.
+const FLAKINESS_RUNS: usize = 20;
+
+// The runs so far, in a file with a JSON object for every run on a line of
+// its own, oldest first. New runs are appended to the end.
+pub struct History {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RunRecord {
+    pub started: SystemTime,
+    pub tasks: Vec<TaskRecord>,
+    pub timed_out: bool,
+    pub interrupted: bool,
+}
+
+impl RunRecord {
+    // Without durations or fingerprints, which the summary does not have.
+    pub fn new(summary: &RunSummary, started: SystemTime) -> RunRecord {
+        RunRecord {
+            started,
+            tasks: summary
+                .tasks
+                .iter()
+                .map(|(name, status)| TaskRecord {
+                    name: name.clone(),
+                    status: status.clone(),
+                    duration: None,
+                    attempts: summary.attempts.get(name).copied().unwrap_or(1),
+                    fingerprint: None,
+                })
+                .collect(),
+            timed_out: summary.timed_out,
+            interrupted: summary.interrupted,
+        }
+    }
+
+    // The tasks that a run that was stopped cancelled are recorded as failed,
+    // so only the runs that were not stopped tell how the tasks do.
+    pub fn is_complete(&self) -> bool {
+        !self.interrupted && !self.timed_out
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TaskRecord {
+    pub name: TaskName,
+    pub status: Status,
+    // How long the task ran, if it ran.
+    pub duration: Option<Duration>,
+    // Runs recorded before attempts and fingerprints were kept have them
+    // missing.
+    #[serde(default = "one_attempt")]
+    pub attempts: u32,
+    // A hash of the inputs of the task, see fingerprints.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+fn one_attempt() -> u32 {
+    1
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> History {
+        History { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, run: &RunRecord) -> io::Result<()> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut line = serde_json::to_string(run)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    // No history yet is the same as an empty one. Lines that cannot be read,
+    // like the half a line of a run that was killed while it was added, are
+    // skipped with a warning, so that they do not hide the other runs.
+    pub fn runs(&self) -> io::Result<Vec<RunRecord>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut runs = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(run) => runs.push(run),
+                Err(error) => eprintln!(
+                    "Skipped line {} of {}: {}",
+                    index + 1,
+                    self.path.display(),
+                    error
+                ),
+            }
+        }
+        Ok(runs)
+    }
+
+    // How long every task took the last time it finished.
+    pub fn previous_durations(&self) -> io::Result<HashMap<TaskName, Duration>> {
+        let mut durations = HashMap::new();
+        for run in self.runs()? {
+            for task in run.tasks {
+                if let (Status::Finished(_), Some(duration)) = (&task.status, task.duration) {
+                    durations.insert(task.name, duration);
+                }
+            }
+        }
+        Ok(durations)
+    }
+}
+
+// A task that did worse in the last run than in the runs before it.
+#[derive(PartialEq, Debug)]
+pub enum Regression {
+    // The usual duration is the median of the runs before.
+    Slower {
+        task: TaskName,
+        duration: Duration,
+        usual: Duration,
+    },
+    // The task failed, after it did not fail in any of the runs before.
+    Failing {
+        task: TaskName,
+        error: Error,
+    },
+}
+
+// Compares the last run with at most the given number of runs before it.
+// Runs that were stopped are left out.
+pub fn find_regressions(runs: &[RunRecord], compared_runs: usize) -> Vec<Regression> {
+    let runs: Vec<&RunRecord> = runs.iter().filter(|run| run.is_complete()).collect();
+    let (last, earlier) = match runs.split_last() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let earlier = &earlier[earlier.len().saturating_sub(compared_runs)..];
+    let mut regressions = Vec::new();
+    for task in &last.tasks {
+        let before: Vec<&TaskRecord> = earlier
+            .iter()
+            .filter_map(|run| run.tasks.iter().find(|other| other.name == task.name))
+            .collect();
+        match (&task.status, task.duration) {
+            (Status::Failed(error), _) => {
+                let failed_before = before
+                    .iter()
+                    .any(|other| matches!(other.status, Status::Failed(_)));
+                if !before.is_empty() && !failed_before {
+                    regressions.push(Regression::Failing {
+                        task: task.name.clone(),
+                        error: error.clone(),
+                    });
+                }
+            }
+            (Status::Finished(_), Some(duration)) => {
+                let mut durations: Vec<Duration> = before
+                    .iter()
+                    .filter(|other| matches!(other.status, Status::Finished(_)))
+                    .filter_map(|other| other.duration)
+                    .collect();
+                if durations.is_empty() {
+                    continue;
+                }
+                durations.sort();
+                let usual = durations[durations.len() / 2];
+                if duration.as_secs_f64() > usual.as_secs_f64() * SLOWER_FACTOR
+                    && duration > usual + MIN_SLOWDOWN
+                {
+                    regressions.push(Regression::Slower {
+                        task: task.name.clone(),
+                        duration,
+                        usual,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    regressions
+}
+
+// For example:
+// build: 12.3s, 45% slower than the usual 8.5s
+// test: failed after passing before: Exited with code 1
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Regression::Slower {
+                task,
+                duration,
+                usual,
+            } => write!(
+                f,
+                "{}: {:.1}s, {:.0}% slower than the usual {:.1}s",
+                task,
+                duration.as_secs_f64(),
+                (duration.as_secs_f64() / usual.as_secs_f64() - 1.0) * 100.0,
+                usual.as_secs_f64()
+            ),
+            Regression::Failing { task, error } => {
+                write!(f, "{}: failed after passing before: {}", task, error)
+            }
+        }
+    }
+}
+
+// What the result of every task depends on, as far as the task tells: a
+// hash of its cache key, or else of its inputs. Tasks that tell neither have
+// no fingerprint, and all their runs count as having the same inputs.
+pub fn fingerprints(tasks: &[Box<dyn Task>]) -> HashMap<TaskName, String> {
+    tasks
+        .iter()
+        .filter_map(|task| {
+            let key = task.cache_key().or_else(|| {
+                let files = task.inputs();
+                if files.is_empty() {
+                    None
+                } else {
+                    Some(CacheKey {
+                        files,
+                        ..CacheKey::default()
+                    })
+                }
+            })?;
+            let fingerprint = key.hash(&task.name()).ok()?;
+            Some((task.name(), fingerprint))
+        })
+        .collect()
+}
+
+// How often a task passed and failed with the same inputs. A task that
+// passed after failed attempts counts as having failed for each of them.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Flakiness {
+    pub task: TaskName,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Flakiness {
+    // Counts the complete runs where the task had the given fingerprint.
+    pub fn of(task: &str, fingerprint: Option<&str>, runs: &[RunRecord]) -> Flakiness {
+        let mut flakiness = Flakiness {
+            task: String::from(task),
+            passed: 0,
+            failed: 0,
+        };
+        for run in runs.iter().filter(|run| run.is_complete()) {
+            let record = run
+                .tasks
+                .iter()
+                .find(|record| record.name == task && record.fingerprint.as_deref() == fingerprint);
+            if let Some(record) = record {
+                flakiness.add(&record.status, record.attempts);
+            }
+        }
+        flakiness
+    }
+
+    fn add(&mut self, status: &Status, attempts: u32) {
+        match status {
+            Status::Finished(_) => {
+                self.passed += 1;
+                self.failed += attempts.saturating_sub(1) as usize;
+            }
+            Status::Failed(_) => self.failed += 1,
+            Status::Pending | Status::Running | Status::Skipped => (),
+        }
+    }
+
+    pub fn is_flaky(&self) -> bool {
+        self.passed > 0 && self.failed > 0
+    }
+}
+
+// The flakiness of every task in the runs, with the inputs it had in the
+// last run it was in, in the order the tasks first appear.
+pub fn count_outcomes(runs: &[RunRecord]) -> Vec<Flakiness> {
+    let mut tasks: Vec<(&str, Option<&str>)> = Vec::new();
+    for record in runs.iter().flat_map(|run| &run.tasks) {
+        let fingerprint = record.fingerprint.as_deref();
+        match tasks.iter_mut().find(|(name, _)| *name == record.name) {
+            Some(task) => task.1 = fingerprint,
+            None => tasks.push((&record.name, fingerprint)),
+        }
+    }
+    tasks
+        .into_iter()
+        .map(|(name, fingerprint)| Flakiness::of(name, fingerprint, runs))
+        .collect()
+}
+
+// For example: test: passed 3 of 5 times, flaky
+impl fmt::Display for Flakiness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: passed {} of {} times",
+            self.task,
+            self.passed,
+            self.passed + self.failed
+        )?;
+        if self.is_flaky() {
+            write!(f, ", flaky")?;
+        }
+        Ok(())
+    }
+}
+
+// Passes everything on to another view, and adds the run to the history
+// once it is over. Tells the view about the tasks that turn out flaky when
+// their outcome is added to the earlier runs.
+pub struct HistoryRecorder {
+    view: Box<dyn View>,
+    history: History,
+    // The last runs before this one, to tell if a task is flaky.
+    earlier_runs: Vec<RunRecord>,
+    fingerprints: HashMap<TaskName, String>,
+    started: SystemTime,
+    // The tasks that have run, by name.
+    task_runs: HashMap<TaskName, TaskRun>,
+}
+
+struct TaskRun {
+    started: SystemTime,
+    ended: Option<SystemTime>,
+    attempts: u32,
+    // Whether the view has been told that the task is flaky.
+    flaky: bool,
+}
+
+impl HistoryRecorder {
+    // The fingerprints are those of the tasks about to run. A history that
+    // cannot be read counts as empty here, since the run can be added to it
+    // all the same.
+    pub fn new(
+        history: History,
+        fingerprints: HashMap<TaskName, String>,
+        view: Box<dyn View>,
+    ) -> HistoryRecorder {
+        let mut earlier_runs = history.runs().unwrap_or_default();
+        earlier_runs.drain(..earlier_runs.len().saturating_sub(FLAKINESS_RUNS));
+        HistoryRecorder {
+            view,
+            history,
+            earlier_runs,
+            fingerprints,
+            started: SystemTime::now(),
+            task_runs: HashMap::new(),
+        }
+    }
+
+    fn is_flaky(&self, task_name: &str, status: &Status, attempts: u32) -> bool {
+        let fingerprint = self.fingerprints.get(task_name).map(String::as_str);
+        let mut flakiness = Flakiness::of(task_name, fingerprint, &self.earlier_runs);
+        flakiness.add(status, attempts);
+        flakiness.is_flaky()
+    }
+
+    fn record_of(&self, task: &TaskRecord) -> TaskRecord {
+        let task_run = self.task_runs.get(&task.name);
+        TaskRecord {
+            duration: task_run.and_then(|task_run| {
+                let ended = task_run.ended?;
+                ended.duration_since(task_run.started).ok()
+            }),
+            attempts: task_run.map_or(1, |task_run| task_run.attempts),
+            fingerprint: self.fingerprints.get(&task.name).cloned(),
+            ..task.clone()
+        }
+    }
+}
+
+impl View for HistoryRecorder {
+    fn initialize(&mut self, tasks: Vec<TaskName>) {
+        self.started = SystemTime::now();
+        self.view.initialize(tasks);
+    }
+
+    // A task that runs again, when watching, is timed again.
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        if !task_update.subtask_path.is_empty() {
+            self.view.update(task_update, time);
+            return;
+        }
+        let task_name = &task_update.task_name;
+        match &task_update.change {
+            TaskChange::TaskStatus(Status::Running) => {
+                let task_run = TaskRun {
+                    started: time,
+                    ended: None,
+                    attempts: 1,
+                    flaky: false,
+                };
+                self.task_runs.entry(task_name.clone()).or_insert(task_run);
+            }
+            TaskChange::TaskStatus(status @ (Status::Finished(_) | Status::Failed(_))) => {
+                let attempts = self.task_runs.get(task_name).map_or(1, |run| run.attempts);
+                let is_flaky = self.is_flaky(task_name, status, attempts);
+                if let Some(task_run) = self.task_runs.get_mut(task_name) {
+                    task_run.ended = Some(time);
+                    if is_flaky && !task_run.flaky {
+                        task_run.flaky = true;
+                        let flaky = TaskUpdate {
+                            change: TaskChange::TaskFlaky,
+                            ..task_update.clone()
+                        };
+                        self.view.update(flaky, time);
+                    }
+                }
+            }
+            TaskChange::TaskStatus(Status::Pending | Status::Skipped) => {
+                self.task_runs.remove(task_name);
+            }
+            TaskChange::TaskAttempt(attempt) => {
+                if let Some(task_run) = self.task_runs.get_mut(task_name) {
+                    task_run.attempts = attempt.number;
+                }
+            }
+            TaskChange::TaskFlaky => {
+                if let Some(task_run) = self.task_runs.get_mut(task_name) {
+                    task_run.flaky = true;
+                }
+            }
+            _ => (),
+        }
+        self.view.update(task_update, time);
+    }
+
+    fn add_task(&mut self, task: TaskName, parent: Option<TaskName>) {
+        self.view.add_task(task, parent);
+    }
+
+    fn remove_task(&mut self, task: TaskName) {
+        self.task_runs.remove(&task);
+        self.view.remove_task(task);
+    }
+
+    fn runner_record(&mut self, record: LogRecord, time: SystemTime) {
+        self.view.runner_record(record, time);
+    }
+
+    fn refresh(&mut self, now: SystemTime) {
+        self.view.refresh(now);
+    }
+
+    fn finish(&mut self, summary: &RunSummary) {
+        self.view.finish(summary);
+        let mut run = RunRecord::new(summary, self.started);
+        run.tasks = run.tasks.iter().map(|task| self.record_of(task)).collect();
+        if let Err(error) = self.history.append(&run) {
+            eprintln!(
+                "Could not add the run to {}: {}",
+                self.history.path().display(),
+                error
+            );
+        }
+        // For when the recorder is used for another run, like when
+        // repeating.
+        self.earlier_runs.push(run);
+        self.earlier_runs
+            .drain(..self.earlier_runs.len().saturating_sub(FLAKINESS_RUNS));
+        self.task_runs.clear();
+    }
+}
@@ -1,11 +1,26 @@
 #![feature(internal_output_capture)]
+pub mod bench;
+pub mod cache;
+pub mod cli;
+pub mod command;
 pub mod common;
+pub mod history;
+pub mod log_bridge;
+pub mod plan;
+mod queue;
+pub mod recording;
+pub mod reports;
+pub mod selection;
+pub mod summary;
+pub mod task_file;
 pub mod tasks;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
 pub mod view;
+pub mod watch;
 use common::*;
 use std::thread;
-use tasks::{Logger, Task, TaskResult, TaskRunner};
-use view::*;
+use tasks::{Logger, Task, TaskResult};
 use rand::Rng;
 
 use std::time::Duration;
@@ -17,11 +32,16 @@ struct Problem<'a> {
 }
 
 impl<'a> Task for Problem<'a> {
-    fn run(&self, _: &dyn Logger) -> TaskResult {
-        for val in &self.vals {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        for (index, val) in self.vals.iter().enumerate() {
             //logger.log(val.to_string());
             println!("{}", val);
             thread::sleep(Duration::from_millis(rand::thread_rng().gen_range(500..1500)));
+            logger.progress(Progress::Count {
+                current: index as u64 + 1,
+                total: self.vals.len() as u64,
+                unit: String::from("messages"),
+            });
         }
         return self.result.clone();
     }
@@ -50,7 +70,8 @@ impl Task for PanicProblem {
     }
 }
 
-pub fn run() {
+// The tasks that are run when the binary is not given a task file.
+pub fn demo_tasks() -> Vec<Box<dyn Task>> {
     let p1 = PanicProblem {
         vals: vec![
             String::from("hi"),
@@ -115,24 +136,17 @@ pub fn run() {
         result: Ok(Some(String::from("Import successful."))),
     };
 
-    let problem_runner = TaskRunner {
-        thread_count: 2,
-        view_update_period: 100,
-    };
-    problem_runner.run(
-        vec![
-            Box::from(p01),
-            Box::from(p1),
-            Box::from(p02),
-            Box::from(p03),
-            Box::from(p04),
-            Box::from(p05),
-            Box::from(p2),
-            Box::from(p3),
-            Box::from(p4),
-        ],
-        &mut Console::new(),
-    );
+    vec![
+        Box::from(p01),
+        Box::from(p1),
+        Box::from(p02),
+        Box::from(p03),
+        Box::from(p04),
+        Box::from(p05),
+        Box::from(p2),
+        Box::from(p3),
+        Box::from(p4),
+    ]
 }
 
 // struct DebugConsole {}
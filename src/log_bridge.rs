@@ -0,0 +1,103 @@
+use super::common::*;
+use super::tasks::Logger;
+use log::kv::{Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::cell::RefCell;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Only the most recent records logged outside of the tasks are kept, in case
+// nothing is running to pass them on to a view.
+const MAX_RUNNER_RECORDS: usize = 1000;
+
+thread_local! {
+    static TASK_LOGGER: RefCell<Option<Box<dyn Logger>>> = RefCell::new(None);
+}
+
+static RUNNER_RECORDS: Mutex<Vec<(SystemTime, LogRecord)>> = Mutex::new(Vec::new());
+
+// Passes the records of the log crate on to the task running on the current
+// thread, the same way as printed text is captured. Records logged on any
+// other thread, including threads spawned by a task, end up in the log of
+// the runner itself.
+pub struct LogBridge;
+
+impl LogBridge {
+    pub fn install(max_level: LevelFilter) -> Result<(), SetLoggerError> {
+        log::set_logger(&LogBridge)?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for LogBridge {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let level = to_level(record.level());
+        let message = record.args().to_string();
+        let mut fields = FieldCollector { fields: Vec::new() };
+        let _ = record.key_values().visit(&mut fields);
+        let logged_by_task = with_task_logger(|logger| {
+            let fields: Vec<(&str, &str)> = fields
+                .fields
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            logger.record(level, &message, &fields);
+        });
+        if logged_by_task.is_none() {
+            add_runner_record(LogRecord {
+                level,
+                message,
+                fields: fields.fields,
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+struct FieldCollector {
+    fields: Vec<(String, String)>,
+}
+
+impl<'kvs> VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.fields.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+fn to_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Trace => Level::Trace,
+        log::Level::Debug => Level::Debug,
+        log::Level::Info => Level::Info,
+        log::Level::Warn => Level::Warn,
+        log::Level::Error => Level::Error,
+    }
+}
+
+pub(crate) fn set_task_logger(logger: Box<dyn Logger>) {
+    TASK_LOGGER.with(|task_logger| *task_logger.borrow_mut() = Some(logger));
+}
+
+// Calls f with the logger of the task running on the current thread, if any.
+pub(crate) fn with_task_logger<R>(f: impl FnOnce(&dyn Logger) -> R) -> Option<R> {
+    TASK_LOGGER.with(|logger| logger.borrow().as_deref().map(f))
+}
+
+pub(crate) fn add_runner_record(record: LogRecord) {
+    let mut runner_records = RUNNER_RECORDS.lock().unwrap();
+    if runner_records.len() >= MAX_RUNNER_RECORDS {
+        runner_records.remove(0);
+    }
+    runner_records.push((SystemTime::now(), record));
+}
+
+pub(crate) fn take_runner_records() -> Vec<(SystemTime, LogRecord)> {
+    RUNNER_RECORDS.lock().unwrap().drain(..).collect()
+}
@@ -1,3 +1,7 @@
+use std::env;
+use std::process;
+
 fn main() {
-    console_runner::run()
+    let args: Vec<String> = env::args().skip(1).collect();
+    process::exit(console_runner::cli::run(&args))
 }
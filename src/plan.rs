@@ -0,0 +1,136 @@
+use super::common::*;
+use super::tasks::{Priority, Task};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fmt;
+
+// What a run would do, without running anything. The tasks are grouped in
+// levels: a task is on the level after the last of its dependencies, so the
+// tasks on the same level can run in parallel.
+#[derive(Serialize, Eq, PartialEq, Debug)]
+pub struct Plan {
+    // In the order they would be started.
+    pub tasks: Vec<PlannedTask>,
+    pub thread_count: u16,
+}
+
+#[derive(Serialize, Eq, PartialEq, Debug)]
+pub struct PlannedTask {
+    pub name: TaskName,
+    pub level: usize,
+    pub priority: Priority,
+    pub dependencies: Vec<TaskName>,
+}
+
+impl Plan {
+    pub fn new(tasks: &[Box<dyn Task>], thread_count: u16) -> Plan {
+        let names: Vec<TaskName> = tasks.iter().map(|task| task.name()).collect();
+        let dependencies: Vec<Vec<usize>> = tasks
+            .iter()
+            .map(|task| {
+                task.dependencies()
+                    .iter()
+                    .filter_map(|dependency| names.iter().position(|name| name == dependency))
+                    .collect()
+            })
+            .collect();
+        // Dependencies that are not part of the run are left out, and the
+        // levels of tasks that depend on each other stop growing after as
+        // many rounds as there are tasks.
+        let mut levels = vec![0; tasks.len()];
+        for _ in 0..tasks.len() {
+            for task in 0..tasks.len() {
+                levels[task] = dependencies[task]
+                    .iter()
+                    .map(|dependency| levels[*dependency] + 1)
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+        let mut planned: Vec<(usize, PlannedTask)> = tasks
+            .iter()
+            .enumerate()
+            .map(|(order, task)| {
+                let planned_task = PlannedTask {
+                    name: task.name(),
+                    level: levels[order],
+                    priority: task.priority(),
+                    dependencies: task.dependencies(),
+                };
+                (order, planned_task)
+            })
+            .collect();
+        planned.sort_by_key(|(order, task)| (task.level, Reverse(task.priority), *order));
+        Plan {
+            tasks: planned.into_iter().map(|(_, task)| task).collect(),
+            thread_count,
+        }
+    }
+
+    pub fn levels(&self) -> Vec<Vec<&PlannedTask>> {
+        let mut levels: Vec<Vec<&PlannedTask>> = Vec::new();
+        for task in &self.tasks {
+            if levels.len() <= task.level {
+                levels.resize(task.level + 1, Vec::new());
+            }
+            levels[task.level].push(task);
+        }
+        levels
+    }
+
+    // The dependency graph in the DOT language of Graphviz, with the tasks on
+    // the same level next to each other.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tasks {\n    rankdir = LR;\n");
+        for level in self.levels() {
+            let names: Vec<String> = level.iter().map(|task| quote(&task.name)).collect();
+            dot.push_str(&format!("    {{ rank = same; {}; }}\n", names.join("; ")));
+        }
+        for task in &self.tasks {
+            for dependency in &task.dependencies {
+                dot.push_str(&format!(
+                    "    {} -> {};\n",
+                    quote(dependency),
+                    quote(&task.name)
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// For example:
+// 3 tasks in 2 levels, on 2 threads
+// Level 1: build, lint
+// Level 2: test (after build)
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let levels = self.levels();
+        write!(
+            f,
+            "{} tasks in {} levels, on {} threads",
+            self.tasks.len(),
+            levels.len(),
+            self.thread_count
+        )?;
+        for (number, level) in levels.iter().enumerate() {
+            let tasks: Vec<String> = level
+                .iter()
+                .map(|task| {
+                    if task.dependencies.is_empty() {
+                        task.name.clone()
+                    } else {
+                        format!("{} (after {})", task.name, task.dependencies.join(", "))
+                    }
+                })
+                .collect();
+            write!(f, "\nLevel {}: {}", number + 1, tasks.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,206 @@
+use super::common::*;
+use super::tasks::{Priority, Task};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+
+// Hands out the tasks with the highest priority first. Tasks with the same
+// priority are handed out in the order they were added.
+//
+// A task is only handed out once all of its dependencies have finished. If a
+// dependency fails, or can never run, the task is handed out to be skipped
+// instead.
+//
+// Tasks submitted while the runner is running are held back until the view
+// has been told about them, so that the view never gets an update for a task
+// it does not know of.
+pub struct TaskQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+pub struct SubmittedTask {
+    pub task: Box<dyn Task>,
+    pub parent: Option<TaskName>,
+}
+
+// What a worker should do with the task it was handed.
+pub enum NextTask {
+    Run(Box<dyn Task>),
+    Skip(Box<dyn Task>),
+}
+
+struct QueueState {
+    tasks: Vec<QueuedTask>,
+    // The statuses the tasks that are done ended with, by name.
+    results: HashMap<TaskName, Status>,
+    submitted_tasks: Vec<SubmittedTask>,
+    // Submitted tasks that have not been scheduled yet, including the ones
+    // that are being announced to the view.
+    nbr_of_unscheduled_tasks: usize,
+    nbr_of_added_tasks: usize,
+    nbr_of_running_tasks: usize,
+    closed: bool,
+}
+
+struct QueuedTask {
+    priority: Priority,
+    order: usize,
+    dependencies: Vec<TaskName>,
+    task: Box<dyn Task>,
+}
+
+impl QueuedTask {
+    fn key(&self) -> (Priority, Reverse<usize>) {
+        (self.priority, Reverse(self.order))
+    }
+}
+
+impl QueueState {
+    fn push(&mut self, task: Box<dyn Task>) {
+        self.tasks.push(QueuedTask {
+            priority: task.priority(),
+            order: self.nbr_of_added_tasks,
+            dependencies: task.dependencies(),
+            task,
+        });
+        self.nbr_of_added_tasks += 1;
+    }
+
+    fn is_done(&self) -> bool {
+        self.nbr_of_running_tasks == 0 && self.nbr_of_unscheduled_tasks == 0
+    }
+
+    fn has_failed_dependency(&self, queued: &QueuedTask) -> bool {
+        queued.dependencies.iter().any(|dependency| {
+            self.results
+                .get(dependency)
+                .is_some_and(|status| !matches!(status, Status::Finished(_)))
+        })
+    }
+
+    fn has_finished_dependencies(&self, queued: &QueuedTask) -> bool {
+        queued
+            .dependencies
+            .iter()
+            .all(|dependency| matches!(self.results.get(dependency), Some(Status::Finished(_))))
+    }
+
+    fn take(&mut self, position: usize) -> Box<dyn Task> {
+        self.tasks.swap_remove(position).task
+    }
+
+    // A skipped task counts as failed for the tasks that depend on it.
+    fn take_to_skip(&mut self, position: usize) -> NextTask {
+        let task = self.take(position);
+        self.results.insert(task.name(), Status::Skipped);
+        NextTask::Skip(task)
+    }
+}
+
+impl TaskQueue {
+    // The results are the statuses of the tasks that are already done, for
+    // tasks that depend on them.
+    pub fn new(tasks: Vec<Box<dyn Task>>, results: HashMap<TaskName, Status>) -> TaskQueue {
+        let mut state = QueueState {
+            tasks: Vec::new(),
+            results,
+            submitted_tasks: Vec::new(),
+            nbr_of_unscheduled_tasks: 0,
+            nbr_of_added_tasks: 0,
+            nbr_of_running_tasks: 0,
+            closed: false,
+        };
+        tasks.into_iter().for_each(|task| state.push(task));
+        TaskQueue {
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+        }
+    }
+
+    // Waits until there is a task to run or skip. Returns None once the
+    // queue is empty and no running task can add more tasks to it.
+    pub fn next(&self) -> Option<NextTask> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            let failed = state
+                .tasks
+                .iter()
+                .position(|queued| state.has_failed_dependency(queued));
+            if let Some(position) = failed {
+                return Some(state.take_to_skip(position));
+            }
+            let ready = (0..state.tasks.len())
+                .filter(|position| state.has_finished_dependencies(&state.tasks[*position]))
+                .max_by_key(|position| state.tasks[*position].key());
+            if let Some(position) = ready {
+                state.nbr_of_running_tasks += 1;
+                return Some(NextTask::Run(state.take(position)));
+            }
+            if state.is_done() {
+                // Nothing that is left can ever run, since its dependencies
+                // are not part of the run or depend on each other.
+                if state.tasks.is_empty() {
+                    return None;
+                }
+                return Some(state.take_to_skip(0));
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    pub fn task_done(&self, task_name: TaskName, status: Status) {
+        let mut state = self.state.lock().unwrap();
+        state.nbr_of_running_tasks -= 1;
+        state.results.insert(task_name, status);
+        self.changed.notify_all();
+    }
+
+    // The answers of the given tasks that have finished, in the given order.
+    pub fn answers(&self, task_names: &[TaskName]) -> Vec<(TaskName, Answer)> {
+        let state = self.state.lock().unwrap();
+        task_names
+            .iter()
+            .filter_map(|task_name| match state.results.get(task_name) {
+                Some(Status::Finished(answer)) => Some((task_name.clone(), answer.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn submit(&self, task: Box<dyn Task>, parent: Option<TaskName>) {
+        let mut state = self.state.lock().unwrap();
+        state.submitted_tasks.push(SubmittedTask { task, parent });
+        state.nbr_of_unscheduled_tasks += 1;
+    }
+
+    pub fn take_submitted(&self) -> Vec<SubmittedTask> {
+        self.state.lock().unwrap().submitted_tasks.drain(..).collect()
+    }
+
+    // Makes tasks that have been announced to the view available to run. The
+    // tasks are given back if the queue has been closed in the meantime.
+    pub fn schedule(&self, tasks: Vec<Box<dyn Task>>) -> Vec<Box<dyn Task>> {
+        let mut state = self.state.lock().unwrap();
+        state.nbr_of_unscheduled_tasks -= tasks.len();
+        self.changed.notify_all();
+        if state.closed {
+            return tasks;
+        }
+        tasks.into_iter().for_each(|task| state.push(task));
+        Vec::new()
+    }
+
+    // Stops handing out tasks and returns the ones that were never started.
+    pub fn close(&self) -> Vec<Box<dyn Task>> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        self.changed.notify_all();
+        let mut tasks: Vec<QueuedTask> = state.tasks.drain(..).collect();
+        tasks.sort_by_key(|queued| Reverse(queued.key()));
+        tasks.into_iter().map(|queued| queued.task).collect()
+    }
+}
@@ -0,0 +1,162 @@
+use super::common::*;
+use super::summary::RunSummary;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Written first, to tell a recording apart from other files.
+const HEADER: &[u8] = b"console-runner recording 1\n";
+// How often a replay refreshes the view while waiting for the next event.
+const REFRESH_PERIOD: Duration = Duration::from_millis(100);
+
+// A call to a view, as it is kept in a recording.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum RecordedEvent {
+    Initialize(Vec<TaskName>),
+    Update(TaskUpdate),
+    AddTask(TaskName, Option<TaskName>),
+    RemoveTask(TaskName),
+    RunnerRecord(LogRecord),
+    Finish(RunSummary),
+}
+
+// Passes everything on to another view, and writes it to a file with the
+// time since the recording started. Updates are kept with the time they
+// happened, the rest with the time they were passed on.
+pub struct Recorder {
+    view: Box<dyn View>,
+    file: BufWriter<File>,
+    started: SystemTime,
+    // The first write that failed. Nothing more is written after it.
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, view: Box<dyn View>) -> io::Result<Recorder> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(HEADER)?;
+        Ok(Recorder {
+            view,
+            file,
+            started: SystemTime::now(),
+            error: None,
+        })
+    }
+
+    fn write(&mut self, event: RecordedEvent, time: SystemTime) {
+        if self.error.is_some() {
+            return;
+        }
+        let offset = time.duration_since(self.started).unwrap_or_default();
+        if let Err(error) = bincode::serialize_into(&mut self.file, &(offset, event)) {
+            self.error = Some(io::Error::other(error));
+        }
+    }
+}
+
+impl View for Recorder {
+    fn initialize(&mut self, tasks: Vec<TaskName>) {
+        self.write(RecordedEvent::Initialize(tasks.clone()), SystemTime::now());
+        self.view.initialize(tasks);
+    }
+
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        self.write(RecordedEvent::Update(task_update.clone()), time);
+        self.view.update(task_update, time);
+    }
+
+    fn add_task(&mut self, task: TaskName, parent: Option<TaskName>) {
+        let event = RecordedEvent::AddTask(task.clone(), parent.clone());
+        self.write(event, SystemTime::now());
+        self.view.add_task(task, parent);
+    }
+
+    fn remove_task(&mut self, task: TaskName) {
+        self.write(RecordedEvent::RemoveTask(task.clone()), SystemTime::now());
+        self.view.remove_task(task);
+    }
+
+    fn runner_record(&mut self, record: LogRecord, time: SystemTime) {
+        self.write(RecordedEvent::RunnerRecord(record.clone()), time);
+        self.view.runner_record(record, time);
+    }
+
+    fn refresh(&mut self, now: SystemTime) {
+        self.view.refresh(now);
+    }
+
+    // The recording is complete once the run is over, and errors are shown
+    // then, since showing them earlier would mess up the view.
+    fn finish(&mut self, summary: &RunSummary) {
+        self.write(RecordedEvent::Finish(summary.clone()), SystemTime::now());
+        self.view.finish(summary);
+        let flushed = match self.error.take() {
+            Some(error) => Err(error),
+            None => self.file.flush(),
+        };
+        if let Err(error) = flushed {
+            eprintln!("Could not record the run: {}", error);
+        }
+    }
+}
+
+// The events of a recording, with their time since the recording started.
+// A recording that was cut off, by a crash or a full disk, ends with half an
+// event, which is left out.
+pub fn load(path: &Path) -> io::Result<Vec<(Duration, RecordedEvent)>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut header = vec![0; HEADER.len()];
+    if file.read_exact(&mut header).is_err() || header != HEADER {
+        let message = "not a recording";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    let mut events = Vec::new();
+    while !file.fill_buf()?.is_empty() {
+        match bincode::deserialize_from(&mut file) {
+            Ok(event) => events.push(event),
+            Err(error) => match *error {
+                bincode::ErrorKind::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    break
+                }
+                error => return Err(io::Error::other(error)),
+            },
+        }
+    }
+    Ok(events)
+}
+
+// Passes the events to the view with the same time between them as when they
+// were recorded, divided by the speed. An infinite speed replays everything
+// at once. Returns the summary of the run, if the recording has it.
+pub fn replay(
+    events: Vec<(Duration, RecordedEvent)>,
+    speed: f64,
+    view: &mut dyn View,
+) -> Option<RunSummary> {
+    let mut run_summary = None;
+    let started = Instant::now();
+    let replay_started = SystemTime::now();
+    for (offset, event) in events {
+        let due = offset.div_f64(speed);
+        while started.elapsed() < due {
+            thread::sleep(REFRESH_PERIOD.min(due.saturating_sub(started.elapsed())));
+            view.refresh(SystemTime::now());
+        }
+        let time = replay_started + due;
+        match event {
+            RecordedEvent::Initialize(tasks) => view.initialize(tasks),
+            RecordedEvent::Update(task_update) => view.update(task_update, time),
+            RecordedEvent::AddTask(task, parent) => view.add_task(task, parent),
+            RecordedEvent::RemoveTask(task) => view.remove_task(task),
+            RecordedEvent::RunnerRecord(record) => view.runner_record(record, time),
+            RecordedEvent::Finish(summary) => {
+                view.finish(&summary);
+                run_summary = Some(summary);
+            }
+        }
+    }
+    run_summary
+}
@@ -0,0 +1,354 @@
+use super::common::*;
+use super::plan::Plan;
+use super::summary::RunSummary;
+use super::view::format_since_start;
+use serde_json::json;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Prints every change as a line of its own, prefixed with the time since the
+// run started and the task, for terminals that cannot redraw and for log
+// files. For example:
+// [+0.012s] [download] Running
+// [+3.201s] [download/unpack] disk almost full
+pub struct PlainReport {
+    run_started: SystemTime,
+}
+
+impl PlainReport {
+    pub fn new() -> PlainReport {
+        PlainReport {
+            run_started: SystemTime::now(),
+        }
+    }
+}
+
+impl Default for PlainReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl View for PlainReport {
+    fn initialize(&mut self, _: Vec<TaskName>) {
+        self.run_started = SystemTime::now();
+    }
+
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        let mut path = vec![task_update.task_name];
+        path.extend(task_update.subtask_path);
+        let prefix = format!(
+            "{}[{}]",
+            format_since_start(time, self.run_started),
+            path.join("/")
+        );
+        match task_update.change {
+            TaskChange::TaskStatus(status) => println!("{} {}", prefix, format_status(&status)),
+            TaskChange::TaskMessage(message) => print_lines(&prefix, &message),
+            TaskChange::TaskStderr(message) => print_lines(&format!("{} stderr:", prefix), &message),
+            TaskChange::TaskRecord(record) => println!("{} {}", prefix, format_record(&record)),
+            TaskChange::TaskAttempt(attempt) => println!(
+                "{} attempt {}/{}",
+                prefix, attempt.number, attempt.max_attempts
+            ),
+            TaskChange::TaskProgress(_) => (),
+            TaskChange::TaskCached => println!("{} Cached", prefix),
+            TaskChange::TaskFlaky => println!("{} Flaky", prefix),
+        }
+    }
+
+    fn remove_task(&mut self, task: TaskName) {
+        println!(
+            "{}[{}] Removed",
+            format_since_start(SystemTime::now(), self.run_started),
+            task
+        );
+    }
+
+    fn runner_record(&mut self, record: LogRecord, time: SystemTime) {
+        println!(
+            "{}[runner] {}",
+            format_since_start(time, self.run_started),
+            format_record(&record)
+        );
+    }
+
+    fn plan(&mut self, plan: &Plan) {
+        println!("{}", plan);
+    }
+}
+
+fn print_lines(prefix: &str, message: &str) {
+    for line in message.split_terminator('\n') {
+        println!("{} {}", prefix, line);
+    }
+}
+
+fn format_status(status: &Status) -> String {
+    match status {
+        Status::Pending => String::from("Pending"),
+        Status::Running => String::from("Running"),
+        Status::Finished(Some(answer)) => format!("Finished: {}", answer),
+        Status::Finished(None) => String::from("Finished"),
+        Status::Failed(error) => format!("Failed: {}", error),
+        Status::Skipped => String::from("Skipped"),
+    }
+}
+
+// For example: WARN disk almost full free=2 GB
+fn format_record(record: &LogRecord) -> String {
+    let fields: String = record
+        .fields
+        .iter()
+        .map(|(key, value)| format!(" {}={}", key, value))
+        .collect();
+    format!("{} {}{}", record.level.name(), record.message, fields)
+}
+
+// Prints every change as a JSON object on a line of its own, so that the run
+// can be followed as it happens, and the summary as the last line. The time
+// is in seconds since the Unix epoch.
+#[derive(Default)]
+pub struct JsonReport {}
+
+impl JsonReport {
+    pub fn new() -> JsonReport {
+        JsonReport {}
+    }
+}
+
+impl View for JsonReport {
+    fn initialize(&mut self, tasks: Vec<TaskName>) {
+        println!("{}", json!({ "tasks": tasks }));
+    }
+
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        println!(
+            "{}",
+            json!({
+                "time": seconds_since_epoch(time),
+                "task_name": task_update.task_name,
+                "subtask_path": task_update.subtask_path,
+                "change": task_update.change,
+            })
+        );
+    }
+
+    fn add_task(&mut self, task: TaskName, parent: Option<TaskName>) {
+        println!("{}", json!({ "added_task": task, "parent": parent }));
+    }
+
+    fn remove_task(&mut self, task: TaskName) {
+        println!("{}", json!({ "removed_task": task }));
+    }
+
+    fn runner_record(&mut self, record: LogRecord, time: SystemTime) {
+        println!(
+            "{}",
+            json!({ "time": seconds_since_epoch(time), "runner_record": record })
+        );
+    }
+
+    fn finish(&mut self, summary: &RunSummary) {
+        println!("{}", json!({ "summary": summary }));
+    }
+
+    fn plan(&mut self, plan: &Plan) {
+        println!("{}", json!({ "plan": plan }));
+    }
+}
+
+fn seconds_since_epoch(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+// Prints a JUnit XML report once the run is over, with a test case for every
+// task, for CI servers that show test results. Every line of the output of a
+// task starts with the time since the run started.
+pub struct JunitReport {
+    cases: Vec<TestCase>,
+    run_started: SystemTime,
+}
+
+struct TestCase {
+    name: TaskName,
+    status: Status,
+    started: Option<SystemTime>,
+    ended: Option<SystemTime>,
+    stdout: String,
+    stderr: String,
+    flaky: bool,
+}
+
+impl TestCase {
+    fn new(name: TaskName) -> TestCase {
+        TestCase {
+            name,
+            status: Status::Pending,
+            started: None,
+            ended: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            flaky: false,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match (self.started, self.ended) {
+            (Some(started), Some(ended)) => ended.duration_since(started).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+impl JunitReport {
+    pub fn new() -> JunitReport {
+        JunitReport {
+            cases: Vec::new(),
+            run_started: SystemTime::now(),
+        }
+    }
+
+    fn get_case(&mut self, name: &str) -> &mut TestCase {
+        let position = match self.cases.iter().position(|case| case.name == name) {
+            Some(position) => position,
+            None => {
+                self.cases.push(TestCase::new(String::from(name)));
+                self.cases.len() - 1
+            }
+        };
+        &mut self.cases[position]
+    }
+}
+
+impl Default for JunitReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl View for JunitReport {
+    fn initialize(&mut self, tasks: Vec<TaskName>) {
+        self.cases = tasks.into_iter().map(TestCase::new).collect();
+        self.run_started = SystemTime::now();
+    }
+
+    // The log of the subtasks is part of the log of their task.
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        let is_subtask = !task_update.subtask_path.is_empty();
+        let timestamp = format_since_start(time, self.run_started);
+        let case = self.get_case(&task_update.task_name);
+        match task_update.change {
+            TaskChange::TaskStatus(_) if is_subtask => (),
+            TaskChange::TaskStatus(status) => {
+                match status {
+                    Status::Running => case.started = case.started.or(Some(time)),
+                    Status::Finished(_) | Status::Failed(_) => case.ended = Some(time),
+                    _ => (),
+                }
+                case.status = status;
+            }
+            TaskChange::TaskMessage(message) => push_lines(&mut case.stdout, &timestamp, &message),
+            TaskChange::TaskStderr(message) => push_lines(&mut case.stderr, &timestamp, &message),
+            TaskChange::TaskRecord(record) => push_lines(
+                &mut case.stdout,
+                &timestamp,
+                &format!("{}\n", format_record(&record)),
+            ),
+            TaskChange::TaskAttempt(attempt) => push_lines(
+                &mut case.stdout,
+                &timestamp,
+                &format!(
+                    "--- attempt {}/{} ---\n",
+                    attempt.number, attempt.max_attempts
+                ),
+            ),
+            TaskChange::TaskFlaky if is_subtask => (),
+            TaskChange::TaskFlaky => case.flaky = true,
+            TaskChange::TaskProgress(_) | TaskChange::TaskCached => (),
+        }
+    }
+
+    fn add_task(&mut self, task: TaskName, _: Option<TaskName>) {
+        self.get_case(&task);
+    }
+
+    fn remove_task(&mut self, task: TaskName) {
+        self.cases.retain(|case| case.name != task);
+    }
+
+    fn finish(&mut self, summary: &RunSummary) {
+        let count = |matches: fn(&Status) -> bool| {
+            self.cases.iter().filter(|case| matches(&case.status)).count()
+        };
+        let total_time: Duration = self.cases.iter().map(TestCase::duration).sum();
+        println!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        println!(
+            r#"<testsuite name="console-runner" tests="{}" failures="{}" skipped="{}" time="{:.3}">"#,
+            self.cases.len(),
+            count(|status| matches!(status, Status::Failed(_))),
+            count(|status| !matches!(status, Status::Finished(_) | Status::Failed(_))),
+            total_time.as_secs_f64()
+        );
+        for case in &self.cases {
+            print_test_case(case);
+        }
+        if summary.interrupted || summary.timed_out {
+            let reason = if summary.interrupted { "interrupted" } else { "timed out" };
+            println!("  <system-err>The run was {}</system-err>", reason);
+        }
+        println!("</testsuite>");
+    }
+}
+
+// Text that continues a line that was not ended yet is not stamped again.
+fn push_lines(output: &mut String, timestamp: &str, text: &str) {
+    for line in text.split_inclusive('\n') {
+        if output.is_empty() || output.ends_with('\n') {
+            output.push_str(timestamp);
+        }
+        output.push_str(line);
+    }
+}
+
+fn print_test_case(case: &TestCase) {
+    println!(
+        r#"  <testcase name="{}" time="{:.3}">"#,
+        escape_xml(&case.name),
+        case.duration().as_secs_f64()
+    );
+    if case.flaky {
+        println!("    <properties>");
+        println!(r#"      <property name="flaky" value="true"/>"#);
+        println!("    </properties>");
+    }
+    match &case.status {
+        Status::Finished(_) => (),
+        Status::Failed(error) => println!(r#"    <failure message="{}"/>"#, escape_xml(error)),
+        Status::Skipped => println!("    <skipped/>"),
+        Status::Pending | Status::Running => {
+            println!(r#"    <skipped message="The task did not finish"/>"#)
+        }
+    }
+    if !case.stdout.is_empty() {
+        println!("    <system-out>{}</system-out>", escape_xml(&case.stdout));
+    }
+    if !case.stderr.is_empty() {
+        println!("    <system-err>{}</system-err>", escape_xml(&case.stderr));
+    }
+    println!("  </testcase>");
+}
+
+// XML cannot have control characters other than tab and line breaks, even
+// escaped, so the others are left out. That includes the escape character of
+// ANSI color codes.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c >= ' ' || matches!(c, '\t' | '\n' | '\r'))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,123 @@
+use super::common::*;
+use super::tasks::Task;
+use regex::Regex;
+
+// Matches tasks by name or tag.
+#[derive(Debug)]
+pub enum Pattern {
+    // A glob like build-* matched against the whole name.
+    Name(glob::Pattern),
+    // A regex matched against any part of the name.
+    Regex(Regex),
+    Tag(String),
+}
+
+impl Pattern {
+    // Parses tag:NAME as a tag, re:REGEX as a regex and anything else as a
+    // glob.
+    pub fn parse(text: &str) -> Result<Pattern, String> {
+        if let Some(tag) = text.strip_prefix("tag:") {
+            Ok(Pattern::Tag(String::from(tag)))
+        } else if let Some(regex) = text.strip_prefix("re:") {
+            Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|error| format!("Invalid regex {}: {}", regex, error))
+        } else {
+            glob::Pattern::new(text)
+                .map(Pattern::Name)
+                .map_err(|error| format!("Invalid glob {}: {}", text, error))
+        }
+    }
+
+    fn matches(&self, task: &dyn Task) -> bool {
+        match self {
+            Pattern::Name(glob) => glob.matches(&task.name()),
+            Pattern::Regex(regex) => regex.is_match(&task.name()),
+            Pattern::Tag(tag) => task.tags().contains(tag),
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Pattern::Name(glob), Pattern::Name(other)) => glob == other,
+            (Pattern::Regex(regex), Pattern::Regex(other)) => regex.as_str() == other.as_str(),
+            (Pattern::Tag(tag), Pattern::Tag(other)) => tag == other,
+            _ => false,
+        }
+    }
+}
+
+// Which of the tasks given to the runner to run. A task is selected if it
+// matches any of the include patterns, or if there are none, and none of the
+// exclude patterns. The tasks that depend on a selected task are selected
+// too, unless they are excluded, so that what is built on a task is checked
+// with it. Then the tasks that the selected tasks depend on are selected,
+// even if they are excluded, since the selected tasks could not run
+// otherwise. Those do not pull in the tasks that depend on them.
+#[derive(PartialEq, Debug)]
+pub struct Selection {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+    // Show the tasks that were not selected as skipped, instead of leaving
+    // them out of the run.
+    pub show_excluded: bool,
+}
+
+impl Selection {
+    pub const ALL: Selection = Selection {
+        include: Vec::new(),
+        exclude: Vec::new(),
+        show_excluded: false,
+    };
+
+    fn matches(&self, task: &dyn Task) -> bool {
+        let included = self.include.is_empty()
+            || self.include.iter().any(|pattern| pattern.matches(task));
+        included && !self.excludes(task)
+    }
+
+    fn excludes(&self, task: &dyn Task) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(task))
+    }
+
+    // Whether each of the tasks is selected.
+    pub fn select(&self, tasks: &[Box<dyn Task>]) -> Vec<bool> {
+        let mut selected: Vec<bool> = tasks
+            .iter()
+            .map(|task| self.matches(task.as_ref()))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (position, task) in tasks.iter().enumerate() {
+                let depends_on_selected = task.dependencies().iter().any(|dependency| {
+                    tasks
+                        .iter()
+                        .zip(&selected)
+                        .any(|(other, selected)| *selected && other.name() == *dependency)
+                });
+                if !selected[position] && depends_on_selected && !self.excludes(task.as_ref()) {
+                    selected[position] = true;
+                    changed = true;
+                }
+            }
+        }
+        let mut unvisited: Vec<TaskName> = tasks
+            .iter()
+            .zip(&selected)
+            .filter(|(_, selected)| **selected)
+            .flat_map(|(task, _)| task.dependencies())
+            .collect();
+        while let Some(dependency) = unvisited.pop() {
+            if let Some(position) = tasks.iter().position(|task| task.name() == dependency) {
+                if !selected[position] {
+                    selected[position] = true;
+                    unvisited.extend(tasks[position].dependencies());
+                }
+            }
+        }
+        selected
+    }
+}
@@ -0,0 +1,136 @@
+use super::common::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_NO_ANSWER: i32 = 2;
+pub const EXIT_TIMED_OUT: i32 = 124;
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+// Decides which outcomes of a run should make the process exit with a non
+// zero code.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum ExitPolicy {
+    // Always exit with 0, no matter how the tasks went.
+    Lenient,
+    // Exit with a non zero code if a task failed or the run was stopped.
+    #[default]
+    Normal,
+    // Like Normal, but also fail if a task finished without an answer.
+    Strict,
+}
+
+// The final status of every task in a run, in the order the tasks were given
+// to the runner.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct RunSummary {
+    pub tasks: Vec<(TaskName, Status)>,
+    pub timed_out: bool,
+    pub interrupted: bool,
+    // How many attempts the tasks that were retried took, by name.
+    pub attempts: HashMap<TaskName, u32>,
+}
+
+impl RunSummary {
+    pub fn new(task_names: Vec<TaskName>) -> RunSummary {
+        RunSummary {
+            tasks: task_names
+                .into_iter()
+                .map(|name| (name, Status::Pending))
+                .collect(),
+            timed_out: false,
+            interrupted: false,
+            attempts: HashMap::new(),
+        }
+    }
+
+    pub fn add_task(&mut self, task_name: TaskName) {
+        self.tasks.push((task_name, Status::Pending));
+    }
+
+    pub fn remove_task(&mut self, task_name: &str) {
+        self.tasks.retain(|(name, _)| name != task_name);
+        self.attempts.remove(task_name);
+    }
+
+    // A task that is run again, when watching, starts counting its attempts
+    // again.
+    pub fn set_status(&mut self, task_name: &str, status: Status) {
+        if matches!(status, Status::Pending | Status::Running) {
+            self.attempts.remove(task_name);
+        }
+        if let Some(task) = self.tasks.iter_mut().find(|(name, _)| name == task_name) {
+            task.1 = status;
+        }
+    }
+
+    pub fn set_attempt(&mut self, task_name: &str, attempt: u32) {
+        self.attempts.insert(String::from(task_name), attempt);
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.tasks
+            .iter()
+            .any(|(_, status)| matches!(status, Status::Failed(_)))
+    }
+
+    pub fn has_missing_answers(&self) -> bool {
+        self.tasks
+            .iter()
+            .any(|(_, status)| matches!(status, Status::Finished(None)))
+    }
+
+    pub fn exit_code(&self, policy: ExitPolicy) -> i32 {
+        if policy == ExitPolicy::Lenient {
+            EXIT_SUCCESS
+        } else if self.interrupted {
+            EXIT_INTERRUPTED
+        } else if self.timed_out {
+            EXIT_TIMED_OUT
+        } else if self.has_failures() {
+            EXIT_FAILED
+        } else if policy == ExitPolicy::Strict && self.has_missing_answers() {
+            EXIT_NO_ANSWER
+        } else {
+            EXIT_SUCCESS
+        }
+    }
+}
+
+// For example:
+// Finished  hello?: 42
+// Failed    doing stuff: Something went wrong!
+// 2 tasks: 1 finished, 1 failed, 0 skipped
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, status) in &self.tasks {
+            match status {
+                Status::Pending => writeln!(f, "Pending   {}", name)?,
+                Status::Running => writeln!(f, "Running   {}", name)?,
+                Status::Finished(Some(answer)) => writeln!(f, "Finished  {}: {}", name, answer)?,
+                Status::Finished(None) => writeln!(f, "Finished  {}", name)?,
+                Status::Failed(error) => writeln!(f, "Failed    {}: {}", name, error)?,
+                Status::Skipped => writeln!(f, "Skipped   {}", name)?,
+            }
+        }
+        let count = |matches: fn(&Status) -> bool| {
+            self.tasks.iter().filter(|(_, status)| matches(status)).count()
+        };
+        write!(
+            f,
+            "{} tasks: {} finished, {} failed, {} skipped",
+            self.tasks.len(),
+            count(|status| matches!(status, Status::Finished(_))),
+            count(|status| matches!(status, Status::Failed(_))),
+            count(|status| matches!(status, Status::Skipped)),
+        )?;
+        if self.interrupted {
+            write!(f, " (interrupted)")?;
+        } else if self.timed_out {
+            write!(f, " (timed out)")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use super::cache::CacheKey;
+use super::cli::parse_duration;
+use super::command::CommandTask;
+use super::tasks::{RetryPolicy, Task};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml::Spanned;
+
+// A TOML file with a [[task]] table for every task, run in the order they
+// are listed unless their dependencies say otherwise. For example:
+//
+// [[task]]
+// name = "test"
+// command = "cargo"
+// args = ["test", "--workspace"]
+// env = { RUST_BACKTRACE = "1" }
+// working_dir = "crates/app"
+// depends_on = ["build"]
+// timeout = "10m"
+// retries = 2
+// tags = ["ci"]
+// inputs = ["crates/app/src"]
+// cache = { files = ["crates/app"], params = { profile = "dev" }, version = "1" }
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskFile {
+    #[serde(default)]
+    task: Vec<TaskEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskEntry {
+    name: Spanned<String>,
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    working_dir: Option<PathBuf>,
+    #[serde(default)]
+    depends_on: Vec<Spanned<String>>,
+    timeout: Option<Spanned<String>>,
+    // How many times the task is run again if it fails.
+    retries: Option<Spanned<u32>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    // The files and directories to watch, relative to where the runner is
+    // started like the working dir.
+    #[serde(default)]
+    inputs: Vec<PathBuf>,
+    // For tasks whose result only depends on these.
+    cache: Option<CacheEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CacheEntry {
+    #[serde(default)]
+    files: Vec<PathBuf>,
+    #[serde(default)]
+    params: BTreeMap<String, String>,
+    #[serde(default)]
+    version: String,
+}
+
+pub fn load(path: &Path) -> Result<Vec<Box<dyn Task>>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+    parse(&content, &path.display().to_string())
+}
+
+// The origin is where the content comes from, to point out errors with.
+pub fn parse(content: &str, origin: &str) -> Result<Vec<Box<dyn Task>>, String> {
+    let task_file: TaskFile =
+        toml::from_str(content).map_err(|error| format!("{}: {}", origin, error))?;
+    let error_at = |span: Range<usize>, entry: &TaskEntry, field: &str, message: String| {
+        let line = content[..span.start].matches('\n').count() + 1;
+        format!(
+            "{}:{}: task `{}`, field `{}`: {}",
+            origin,
+            line,
+            entry.name.get_ref(),
+            field,
+            message
+        )
+    };
+    for (index, entry) in task_file.task.iter().enumerate() {
+        if task_file.task[..index]
+            .iter()
+            .any(|other| other.name.get_ref() == entry.name.get_ref())
+        {
+            let message = String::from("there is already a task with this name");
+            return Err(error_at(entry.name.span(), entry, "name", message));
+        }
+        for dependency in &entry.depends_on {
+            if !task_file
+                .task
+                .iter()
+                .any(|other| other.name.get_ref() == dependency.get_ref())
+            {
+                let message = format!("there is no task named `{}`", dependency.get_ref());
+                return Err(error_at(dependency.span(), entry, "depends_on", message));
+            }
+            if depends_on(&task_file.task, dependency.get_ref(), entry.name.get_ref()) {
+                let message = format!("`{}` depends on this task", dependency.get_ref());
+                return Err(error_at(dependency.span(), entry, "depends_on", message));
+            }
+        }
+        if let Some(timeout) = &entry.timeout {
+            parse_duration(timeout.get_ref())
+                .map_err(|message| error_at(timeout.span(), entry, "timeout", message))?;
+        }
+        if let Some(retries) = &entry.retries {
+            if retries.get_ref().checked_add(1).is_none() {
+                let message = format!("at most {} retries are possible", u32::MAX - 1);
+                return Err(error_at(retries.span(), entry, "retries", message));
+            }
+        }
+    }
+    Ok(task_file
+        .task
+        .into_iter()
+        .map(|entry| Box::from(to_task(entry)) as Box<dyn Task>)
+        .collect())
+}
+
+// Whether the task depends on the other task, directly or through other
+// tasks.
+fn depends_on(entries: &[TaskEntry], task: &str, other: &str) -> bool {
+    let mut unvisited = vec![task];
+    let mut visited = Vec::new();
+    while let Some(task) = unvisited.pop() {
+        if task == other {
+            return true;
+        }
+        if visited.contains(&task) {
+            continue;
+        }
+        visited.push(task);
+        if let Some(entry) = entries.iter().find(|entry| entry.name.get_ref() == task) {
+            unvisited.extend(entry.depends_on.iter().map(|dependency| dependency.get_ref().as_str()));
+        }
+    }
+    false
+}
+
+fn to_task(entry: TaskEntry) -> CommandTask {
+    let mut command = Command::new(&entry.command);
+    command.args(&entry.args).envs(&entry.env);
+    if let Some(working_dir) = &entry.working_dir {
+        command.current_dir(working_dir);
+    }
+    let mut task = CommandTask::new(entry.name.get_ref(), command);
+    task.retry_policy = RetryPolicy {
+        max_attempts: entry.retries.map_or(0, Spanned::into_inner) + 1,
+        ..RetryPolicy::default()
+    };
+    task.timeout = entry
+        .timeout
+        .and_then(|timeout| parse_duration(timeout.get_ref()).ok());
+    task.dependencies = entry
+        .depends_on
+        .into_iter()
+        .map(Spanned::into_inner)
+        .collect();
+    task.tags = entry.tags;
+    task.inputs = entry.inputs;
+    task.cache_key = entry.cache.map(|cache| CacheKey {
+        files: cache.files,
+        params: cache.params.into_iter().collect(),
+        version: cache.version,
+        ..CacheKey::default()
+    });
+    task
+}
@@ -1,14 +1,25 @@
+use super::cache::{CacheKey, ResultCache};
 use super::common::*;
+use super::log_bridge;
+use super::plan::Plan;
+use super::queue::{NextTask, TaskQueue};
+use super::selection::Selection;
+use super::summary::RunSummary;
+use super::watch::InputWatcher;
 use logos::Lexer;
 use logos::Logos;
-use std::collections::VecDeque;
+use signal_hook::consts::{SIGINT, SIGKILL};
+use signal_hook::SigId;
+use std::collections::HashMap;
 use std::io::set_output_capture;
 use std::panic;
+use std::path::{Path, PathBuf};
 use std::str::from_utf8;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use std::thread;
-use std::thread::JoinHandle;
-use std::time::Duration;
+use std::thread::{JoinHandle, ThreadId};
+use std::time::{Duration, Instant, SystemTime};
 
 // We are locked to using this datatype to send messages since it is the
 // datatype used for set_output_capture. We use the same buffer for everything
@@ -19,22 +30,279 @@ use std::time::Duration;
 type LocalStream = Arc<Mutex<Vec<u8>>>;
 pub type TaskResult = Result<Answer, Error>;
 
+// How many runs are catching Ctrl-C, and a flag that is set while none are,
+// for the handler that then kills the process as if there were no handlers.
+static CATCHING_RUNS: Mutex<usize> = Mutex::new(0);
+static NOT_CATCHING: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+// How often what a task prints is taken from its capture, and stamped with
+// the time it was taken.
+const CAPTURE_PERIOD: Duration = Duration::from_millis(10);
+
 pub trait Task: Send {
     fn run(&self, logger: &dyn Logger) -> TaskResult;
     fn name(&self) -> TaskName;
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    // Tasks with a higher priority are started first, for example to get the
+    // long running tasks going early. An estimated cost works well here.
+    fn priority(&self) -> Priority {
+        0
+    }
+
+    // The names of the tasks that have to finish before this task can start.
+    // The task is skipped if one of them fails or is skipped.
+    fn dependencies(&self) -> Vec<TaskName> {
+        Vec::new()
+    }
+
+    // Labels to select tasks by, like "slow" or "network".
+    fn tags(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // The files and directories the task reads. When watching, the task is
+    // run again once one of them changes.
+    fn inputs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    // Only deterministic tasks should have a cache key, since the runner
+    // gives the cached result of the task instead of running it as long as
+    // the key stays the same.
+    fn cache_key(&self) -> Option<CacheKey> {
+        None
+    }
+}
+
+pub type Priority = i32;
+
+// How many times a failing task is run before it is given up on, and how
+// long to wait between the attempts.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    // When false, only tasks that return an error are retried.
+    pub retry_on_panic: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum Backoff {
+    Fixed(Duration),
+    // The delay is doubled after every attempt, starting at the given one.
+    Exponential(Duration),
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            backoff: Backoff::Fixed(Duration::ZERO),
+            retry_on_panic: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay_after(&self, attempt: u32) -> Duration {
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential(delay) => delay * 2u32.saturating_pow(attempt - 1),
+        }
+    }
 }
 
 pub trait Logger {
     fn log(&self, message: &str);
+
+    // Logs diagnostics of the task as its stderr. This is the only way for a
+    // task that is not a command to write to its stderr. Known limitation:
+    // what it prints with eprintln! ends up in its normal log, since the std
+    // output capture does not tell eprintln! apart from println!.
+    fn log_stderr(&self, message: &str);
+
+    // Logs a message with a level and key/value fields. Messages below the
+    // log level of the runner are dropped.
+    fn record(&self, level: Level, message: &str, fields: &[(&str, &str)]);
+
+    fn trace(&self, message: &str) {
+        self.record(Level::Trace, message, &[]);
+    }
+
+    fn debug(&self, message: &str) {
+        self.record(Level::Debug, message, &[]);
+    }
+
+    fn info(&self, message: &str) {
+        self.record(Level::Info, message, &[]);
+    }
+
+    fn warn(&self, message: &str) {
+        self.record(Level::Warn, message, &[]);
+    }
+
+    fn error(&self, message: &str) {
+        self.record(Level::Error, message, &[]);
+    }
+
+    // Reports how far along the task is.
+    fn progress(&self, progress: Progress);
+
+    // Adds a task to the run, for when a task discovers more work to do.
+    // Loggers that are not part of a run cannot add it, and warn instead.
+    fn add_task(&self, task: Box<dyn Task>) {
+        self.warn(&format!("Could not add the task {}", task.name()));
+    }
+
+    // Like add_task, but the new task is shown below the task adding it.
+    fn add_child_task(&self, task: Box<dyn Task>) {
+        self.add_task(task);
+    }
+
+    // Opens a subtask with its own status and log, for example for a phase
+    // of a big task. Subtasks can have subtasks of their own. A task, or
+    // subtask, fails if any of its subtasks fail.
+    fn subtask(&self, name: &str) -> Box<dyn Subtask>;
+
+    fn cancellation(&self) -> &Cancellation;
+
+    // Set once the run has been stopped, for example by a timeout. Tasks that
+    // take long should check this now and then and return early.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation().is_cancelled()
+    }
+}
+
+pub trait Subtask: Logger {
+    // A subtask that is dropped without being finished is finished without
+    // an answer, or failed if its task is panicking.
+    fn finish(self: Box<Self>, result: TaskResult);
+}
+
+// Shared by all tasks of a run. Keeps track of the process groups started by
+// the tasks, so that none of them outlive a cancelled run.
+pub struct Cancellation {
+    cancelled: AtomicBool,
+    grace_period: Duration,
+    process_groups: Mutex<Vec<ProcessGroup>>,
+}
+
+// A process group is kept after it has ended, until the task that started it
+// has reported how it ended, so that a cancelled run waits for that report.
+struct ProcessGroup {
+    id: u32,
+    // The thread of the attempt that started it.
+    thread: ThreadId,
+    running: bool,
+    killed: bool,
+}
+
+impl Cancellation {
+    fn new(grace_period: Duration) -> Cancellation {
+        Cancellation {
+            cancelled: AtomicBool::new(false),
+            grace_period,
+            process_groups: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // The grace period of the runner, which commands that time out get too.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_process_group(&self, process_group: u32) {
+        self.process_groups.lock().unwrap().push(ProcessGroup {
+            id: process_group,
+            thread: thread::current().id(),
+            running: true,
+            killed: false,
+        });
+    }
+
+    pub(crate) fn remove_process_group(&self, process_group: u32) {
+        for group in self.process_groups.lock().unwrap().iter_mut() {
+            if group.id == process_group {
+                group.running = false;
+            }
+        }
+    }
+
+    // Called once the task has reported how it ended.
+    fn forget_process_groups(&self, threads: &[ThreadId]) {
+        self.process_groups
+            .lock()
+            .unwrap()
+            .retain(|group| !threads.contains(&group.thread));
+    }
+
+    // Sends SIGKILL to the process groups that are still running, once to
+    // each of them. Returns how many process groups were still running, or
+    // have not been reported by their task yet.
+    fn kill_process_groups(&self) -> usize {
+        let mut process_groups = self.process_groups.lock().unwrap();
+        for process_group in process_groups.iter_mut() {
+            if process_group.running && !process_group.killed {
+                kill_process_group(process_group.id, SIGKILL);
+                process_group.killed = true;
+            }
+        }
+        process_groups.len()
+    }
+}
+
+pub(crate) fn kill_process_group(process_group: u32, signal: i32) {
+    unsafe {
+        libc::kill(-(process_group as i32), signal);
+    }
 }
 
 pub struct TaskRunner {
     pub thread_count: u16,
     pub view_update_period: u64,
+    // The run is stopped if it has not completed within this time. Tasks that
+    // are running at that point are cancelled.
+    pub timeout: Option<Duration>,
+    // Stop handing out new tasks once a task has failed. The tasks that were
+    // not started are marked as skipped, the running ones are left to finish.
+    pub fail_fast: bool,
+    // Log records below this level are dropped.
+    pub log_level: Level,
+    // How long running tasks get to stop once the run has been cancelled by a
+    // timeout or Ctrl-C. Commands are sent SIGTERM when the run is cancelled,
+    // and SIGKILL when this has passed.
+    pub grace_period: Duration,
+    pub selection: Selection,
+    // Where to keep the results of the tasks that have a cache key. Nothing
+    // is cached when not set.
+    pub cache_dir: Option<PathBuf>,
 }
 
 struct ThreadLogger {
     sink: LocalStream,
+    // What the thread running the task prints, until it is sent on to the
+    // sink as a message. Set while an attempt of a task is running.
+    capture: Option<LocalStream>,
+    task_queue: Arc<TaskQueue>,
+    log_level: Level,
+    task_name: Option<TaskName>,
+    // The subtasks, from the outermost one, that the updates are about. The
+    // updates are about the task itself when this is empty.
+    subtask_path: Vec<TaskName>,
+    // The name of the first subtask opened through this logger that failed.
+    failed_subtask: Arc<Mutex<Option<TaskName>>>,
+    cancellation: Arc<Cancellation>,
 }
 
 impl Logger for ThreadLogger {
@@ -43,12 +311,139 @@ impl Logger for ThreadLogger {
         msg.push('\n');
         self.send_update(TaskChange::TaskMessage(msg));
     }
+
+    fn log_stderr(&self, message: &str) {
+        let mut msg = String::from(message);
+        msg.push('\n');
+        self.send_update(TaskChange::TaskStderr(msg));
+    }
+
+    fn record(&self, level: Level, message: &str, fields: &[(&str, &str)]) {
+        if level < self.log_level {
+            return;
+        }
+        self.send_update(TaskChange::TaskRecord(LogRecord {
+            level,
+            message: String::from(message),
+            fields: fields
+                .iter()
+                .map(|(key, value)| (String::from(*key), String::from(*value)))
+                .collect(),
+        }));
+    }
+
+    fn progress(&self, progress: Progress) {
+        self.send_update(TaskChange::TaskProgress(progress));
+    }
+
+    fn add_task(&self, task: Box<dyn Task>) {
+        self.task_queue.submit(task, None);
+    }
+
+    fn add_child_task(&self, task: Box<dyn Task>) {
+        self.task_queue.submit(task, self.task_name.clone());
+    }
+
+    fn subtask(&self, name: &str) -> Box<dyn Subtask> {
+        let mut logger = self.with_new_subtasks();
+        logger.subtask_path.push(String::from(name));
+        logger.set_status(Status::Running);
+        Box::from(ThreadSubtask {
+            logger,
+            parent_failed_subtask: self.failed_subtask.clone(),
+            finished: false,
+        })
+    }
+
+    fn cancellation(&self) -> &Cancellation {
+        &self.cancellation
+    }
 }
 
 impl Clone for ThreadLogger {
     fn clone(&self) -> Self {
         Self {
             sink: self.sink.clone(),
+            capture: self.capture.clone(),
+            task_queue: self.task_queue.clone(),
+            log_level: self.log_level,
+            task_name: self.task_name.clone(),
+            subtask_path: self.subtask_path.clone(),
+            failed_subtask: self.failed_subtask.clone(),
+            cancellation: self.cancellation.clone(),
+        }
+    }
+}
+
+struct ThreadSubtask {
+    logger: ThreadLogger,
+    parent_failed_subtask: Arc<Mutex<Option<TaskName>>>,
+    finished: bool,
+}
+
+impl ThreadSubtask {
+    fn complete(&mut self, result: TaskResult) {
+        let status = self.logger.roll_up(result);
+        if let Status::Failed(_) = status {
+            let mut parent_failed_subtask = self.parent_failed_subtask.lock().unwrap();
+            if parent_failed_subtask.is_none() {
+                *parent_failed_subtask = self.logger.subtask_path.last().cloned();
+            }
+        }
+        self.logger.set_status(status);
+        self.finished = true;
+    }
+}
+
+impl Logger for ThreadSubtask {
+    fn log(&self, message: &str) {
+        self.logger.log(message);
+    }
+
+    fn log_stderr(&self, message: &str) {
+        self.logger.log_stderr(message);
+    }
+
+    fn record(&self, level: Level, message: &str, fields: &[(&str, &str)]) {
+        self.logger.record(level, message, fields);
+    }
+
+    fn progress(&self, progress: Progress) {
+        self.logger.progress(progress);
+    }
+
+    fn add_task(&self, task: Box<dyn Task>) {
+        self.logger.add_task(task);
+    }
+
+    fn add_child_task(&self, task: Box<dyn Task>) {
+        self.logger.add_child_task(task);
+    }
+
+    fn subtask(&self, name: &str) -> Box<dyn Subtask> {
+        self.logger.subtask(name)
+    }
+
+    fn cancellation(&self) -> &Cancellation {
+        self.logger.cancellation()
+    }
+}
+
+impl Subtask for ThreadSubtask {
+    fn finish(mut self: Box<Self>, result: TaskResult) {
+        self.complete(result);
+    }
+}
+
+impl Drop for ThreadSubtask {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if thread::panicking() {
+            self.complete(Err(String::from("Aborting subtask since thread panicked")));
+        } else {
+            self.complete(Ok(None));
         }
     }
 }
@@ -60,8 +455,39 @@ const NAME_CHANGE_END_TAG: &str = " NameChangeEnd}";
 const CLOSE_SINK_TAG: &str = "{CloseSink}";
 
 impl ThreadLogger {
-    fn new(sink: LocalStream) -> ThreadLogger {
-        ThreadLogger { sink }
+    fn new(
+        sink: LocalStream,
+        task_queue: Arc<TaskQueue>,
+        log_level: Level,
+        cancellation: Arc<Cancellation>,
+    ) -> ThreadLogger {
+        ThreadLogger {
+            sink,
+            capture: None,
+            task_queue,
+            log_level,
+            task_name: None,
+            subtask_path: Vec::new(),
+            failed_subtask: Arc::new(Mutex::new(None)),
+            cancellation,
+        }
+    }
+
+    // A logger for the same task that keeps track of its own subtasks.
+    fn with_new_subtasks(&self) -> ThreadLogger {
+        ThreadLogger {
+            failed_subtask: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
+    // A successful result is turned into a failure if a subtask has failed.
+    fn roll_up(&self, result: TaskResult) -> Status {
+        match (result, self.failed_subtask.lock().unwrap().as_ref()) {
+            (Ok(_), Some(subtask)) => Status::Failed(format!("Subtask {} failed", subtask)),
+            (Ok(answer), None) => Status::Finished(answer),
+            (Err(message), _) => Status::Failed(message),
+        }
     }
 
     fn set_status(&self, status: Status) {
@@ -69,13 +495,38 @@ impl ThreadLogger {
     }
 
     fn send_update(&self, change: TaskChange) {
+        self.send_subtask_update(&self.subtask_path, change);
+    }
+
+    // What was printed before the change is sent first, to keep the order.
+    // The sink stays locked from taking the printed text until the change is
+    // written, so that the worker sending the printed text at the same time
+    // cannot write it after the change.
+    fn send_subtask_update(&self, subtask_path: &[TaskName], change: TaskChange) {
         let mut buffer = self.sink.lock().unwrap();
-        buffer.extend_from_slice(TASK_CHANGE_START_TAG.as_bytes());
-        buffer.append(&mut bincode::serialize(&change).unwrap());
-        buffer.extend_from_slice(TASK_CHANGE_END_TAG.as_bytes());
+        self.write_captured(&mut buffer);
+        write_change(&mut buffer, subtask_path, &change);
+    }
+
+    // Sends what the task printed since the last time as a message of the
+    // task, stamped with the time now.
+    fn send_captured(&self) {
+        self.write_captured(&mut self.sink.lock().unwrap());
+    }
+
+    fn write_captured(&self, buffer: &mut Vec<u8>) {
+        let capture = match &self.capture {
+            Some(capture) => capture,
+            None => return,
+        };
+        let text = take_text(&mut capture.lock().unwrap_or_else(PoisonError::into_inner));
+        if !text.is_empty() {
+            write_change(buffer, &[], &TaskChange::TaskMessage(text));
+        }
     }
 
-    fn switch_task(&self, name: TaskName) {
+    fn switch_task(&mut self, name: TaskName) {
+        self.task_name = Some(name.clone());
         let mut buffer = self.sink.lock().unwrap();
         buffer.extend_from_slice(NAME_CHANGE_START_TAG.as_bytes());
         buffer.extend_from_slice(name.as_bytes());
@@ -103,33 +554,305 @@ impl ThreadSink {
 }
 
 impl TaskRunner {
-    pub fn run(&self, tasks: Vec<Box<dyn Task>>, view: &mut dyn View) {
-        if tasks.len() == 0 {
-            return;
+    pub fn run(&self, tasks: Vec<Box<dyn Task>>, view: &mut dyn View) -> RunSummary {
+        let (selected_tasks, task_names, excluded_names) = self.select(tasks);
+        let mut summary = RunSummary::new(task_names.clone());
+        // The view still finishes, so that a report is complete even
+        // when the selection matches no task.
+        if task_names.len() == 0 {
+            view.initialize(task_names);
+            view.finish(&summary);
+            return summary;
+        }
+        view.initialize(task_names);
+        for task_name in excluded_names {
+            skip_excluded_task(&task_name, view);
+            summary.set_status(&task_name, Status::Skipped);
         }
-        view.initialize(tasks.iter().map(|task| task.name()).collect());
-        let task_queue = Arc::new(Mutex::new(VecDeque::from(tasks)));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let signal = catch_interrupts(&interrupted);
+        self.run_round(
+            selected_tasks,
+            HashMap::new(),
+            &interrupted,
+            view,
+            &mut summary,
+        );
+        stop_catching_interrupts(signal);
+        view.finish(&summary);
+        summary
+    }
+
+    // Runs the tasks, then runs them again whenever their inputs change,
+    // until the run is interrupted. A task is used up by running it, so the
+    // tasks are created again for every round. The tasks that are not run
+    // again keep their results, and the tasks waiting for them see those.
+    // The task file, if the tasks come from one, is watched too. Once it
+    // changes, all tasks are run again, and the tasks that are no longer in
+    // it are dropped from the run.
+    pub fn watch(
+        &self,
+        create_tasks: &dyn Fn() -> Vec<Box<dyn Task>>,
+        task_file: Option<&Path>,
+        debounce: Duration,
+        view: &mut dyn View,
+    ) -> Result<RunSummary, String> {
+        let (selected_tasks, task_names, excluded_names) = self.select(create_tasks());
+        let mut watcher = watch_inputs(&selected_tasks, task_file, debounce)?;
+        let mut summary = RunSummary::new(task_names.clone());
+        let mut known_names = task_names.clone();
+        view.initialize(task_names);
+        for task_name in excluded_names {
+            skip_excluded_task(&task_name, view);
+            summary.set_status(&task_name, Status::Skipped);
+        }
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let signal = catch_interrupts(&interrupted);
+        self.run_round(
+            selected_tasks,
+            HashMap::new(),
+            &interrupted,
+            view,
+            &mut summary,
+        );
+        while !summary.interrupted {
+            let changes = watcher.changes(Duration::from_millis(self.view_update_period));
+            summary.interrupted = interrupted.load(Ordering::Relaxed);
+            if (changes.tasks.is_empty() && !changes.task_file) || summary.interrupted {
+                continue;
+            }
+            let (tasks, task_names, excluded_names) = self.select(create_tasks());
+            for task_name in known_names.iter().filter(|name| !task_names.contains(name)) {
+                view.remove_task(task_name.clone());
+                summary.remove_task(task_name);
+            }
+            for task_name in task_names.iter().filter(|name| !known_names.contains(name)) {
+                view.add_task(task_name.clone(), None);
+                summary.add_task(task_name.clone());
+                if excluded_names.contains(task_name) {
+                    skip_excluded_task(task_name, view);
+                    summary.set_status(task_name, Status::Skipped);
+                }
+            }
+            known_names = task_names;
+            if changes.task_file {
+                match watch_inputs(&tasks, task_file, debounce) {
+                    Ok(new_watcher) => watcher = new_watcher,
+                    Err(error) => log::warn!("{}", error),
+                }
+            }
+            let tasks: Vec<Box<dyn Task>> = tasks
+                .into_iter()
+                .filter(|task| changes.task_file || changes.tasks.contains(&task.name()))
+                .collect();
+            let changed: Vec<TaskName> = tasks.iter().map(|task| task.name()).collect();
+            for task_name in &changed {
+                view.update(
+                    TaskUpdate {
+                        task_name: task_name.clone(),
+                        subtask_path: Vec::new(),
+                        change: TaskChange::TaskStatus(Status::Pending),
+                    },
+                    SystemTime::now(),
+                );
+                summary.set_status(task_name, Status::Pending);
+            }
+            let results = summary
+                .tasks
+                .iter()
+                .filter(|(name, _)| !changed.contains(name))
+                .map(|(name, status)| (name.clone(), status.clone()))
+                .collect();
+            self.run_round(tasks, results, &interrupted, view, &mut summary);
+        }
+        stop_catching_interrupts(signal);
+        view.finish(&summary);
+        Ok(summary)
+    }
+
+    // Runs the tasks until they are done or the run is stopped. The results
+    // are the statuses of the tasks that are already done.
+    fn run_round(
+        &self,
+        tasks: Vec<Box<dyn Task>>,
+        results: HashMap<TaskName, Status>,
+        interrupted: &AtomicBool,
+        view: &mut dyn View,
+        summary: &mut RunSummary,
+    ) {
+        let task_queue = Arc::new(TaskQueue::new(tasks, results));
+        let cancellation = Arc::new(Cancellation::new(self.grace_period));
+        let cache = self
+            .cache_dir
+            .as_ref()
+            .map(|cache_dir| Arc::new(ResultCache::new(cache_dir.clone())));
         let mut thread_sinks: Vec<ThreadSink> =
             (0..self.thread_count).map(|_| ThreadSink::new()).collect();
 
         for sink in thread_sinks.iter() {
-            run_tasks_in_thread(task_queue.clone(), sink.print_buffer.clone());
+            run_tasks_in_thread(
+                task_queue.clone(),
+                sink.print_buffer.clone(),
+                self.fail_fast,
+                self.log_level,
+                cancellation.clone(),
+                cache.clone(),
+            );
         }
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut give_up = None;
         while !thread_sinks.is_empty() {
             thread::sleep(Duration::from_millis(self.view_update_period));
-            thread_sinks.retain_mut(|sink| send_changes_to_view(sink, view));
+            // Once the grace period is over, the commands that are still
+            // running are killed, and the run waits a little for them to
+            // report it. Other tasks are left behind.
+            let no_commands_left = give_up.is_some_and(|give_up| Instant::now() >= give_up)
+                && cancellation.kill_process_groups() == 0;
+            let waited_for_kill =
+                give_up.is_some_and(|give_up| Instant::now() >= give_up + KILL_WAIT);
+            thread_sinks
+                .retain_mut(|sink| send_changes_to_view(sink, view, summary, cache.as_deref()));
+            schedule_submitted_tasks(&task_queue, view, summary);
+            send_runner_records_to_view(view);
+            view.refresh(SystemTime::now());
+            summary.interrupted = interrupted.load(Ordering::Relaxed);
+            summary.timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if (summary.interrupted || summary.timed_out) && give_up.is_none() {
+                task_queue.close();
+                cancellation.cancel();
+                give_up = Some(Instant::now() + self.grace_period);
+            }
+            if no_commands_left || waited_for_kill {
+                break;
+            }
+        }
+    }
+
+    // Shows the view what the run would do, without running any task.
+    pub fn plan(&self, tasks: Vec<Box<dyn Task>>, view: &mut dyn View) -> Plan {
+        let (selected_tasks, task_names, excluded_names) = self.select(tasks);
+        let plan = Plan::new(&selected_tasks, self.thread_count);
+        view.initialize(task_names);
+        for task_name in excluded_names {
+            skip_excluded_task(&task_name, view);
         }
+        view.plan(&plan);
+        plan
     }
+
+    // Splits the tasks into the ones to run, the names of the tasks to show,
+    // and the names of the excluded tasks to show as skipped.
+    fn select(
+        &self,
+        tasks: Vec<Box<dyn Task>>,
+    ) -> (Vec<Box<dyn Task>>, Vec<TaskName>, Vec<TaskName>) {
+        let selected = self.selection.select(&tasks);
+        let mut selected_tasks = Vec::new();
+        let mut task_names = Vec::new();
+        let mut excluded_names = Vec::new();
+        for (task, selected) in tasks.into_iter().zip(selected) {
+            if selected {
+                task_names.push(task.name());
+                selected_tasks.push(task);
+            } else if self.selection.show_excluded {
+                task_names.push(task.name());
+                excluded_names.push(task.name());
+            }
+        }
+        (selected_tasks, task_names, excluded_names)
+    }
+}
+
+// How long a cancelled run waits for the commands it has killed.
+const KILL_WAIT: Duration = Duration::from_secs(1);
+
+// Watches the inputs of the tasks, and the task file they come from.
+fn watch_inputs(
+    tasks: &[Box<dyn Task>],
+    task_file: Option<&Path>,
+    debounce: Duration,
+) -> Result<InputWatcher, String> {
+    let mut watcher = InputWatcher::new(tasks, debounce)?;
+    if let Some(task_file) = task_file {
+        watcher.watch_task_file(task_file)?;
+    }
+    Ok(watcher)
+}
+
+fn skip_excluded_task(task_name: &str, view: &mut dyn View) {
+    view.update(
+        TaskUpdate {
+            task_name: String::from(task_name),
+            subtask_path: Vec::new(),
+            change: TaskChange::TaskStatus(Status::Skipped),
+        },
+        SystemTime::now(),
+    );
 }
 
-fn send_changes_to_view(thread_sink: &mut ThreadSink, view: &mut dyn View) -> bool {
+fn send_runner_records_to_view(view: &mut dyn View) {
+    for (time, record) in log_bridge::take_runner_records() {
+        view.runner_record(record, time);
+    }
+}
+
+// Tells the view about the tasks that were added by running tasks before they
+// are handed out.
+fn schedule_submitted_tasks(task_queue: &TaskQueue, view: &mut dyn View, summary: &mut RunSummary) {
+    let mut tasks = Vec::new();
+    for submitted in task_queue.take_submitted() {
+        let task_name = submitted.task.name();
+        view.add_task(task_name.clone(), submitted.parent);
+        summary.add_task(task_name);
+        tasks.push(submitted.task);
+    }
+    for task in task_queue.schedule(tasks) {
+        view.update(
+            TaskUpdate {
+                task_name: task.name(),
+                subtask_path: Vec::new(),
+                change: TaskChange::TaskStatus(Status::Skipped),
+            },
+            SystemTime::now(),
+        );
+        summary.set_status(&task.name(), Status::Skipped);
+    }
+}
+
+fn send_changes_to_view(
+    thread_sink: &mut ThreadSink,
+    view: &mut dyn View,
+    summary: &mut RunSummary,
+    cache: Option<&ResultCache>,
+) -> bool {
     let mut buffer = thread_sink.print_buffer.lock().unwrap();
     for change in get_task_updates(buffer.as_slice()) {
         match change {
-            Change::TaskChange(task_change) => view.update(TaskUpdate {
-                task_name: thread_sink.current_task_name.as_ref().unwrap().clone(),
-                change: task_change,
-            }),
+            Change::TaskChange(subtask_path, time, task_change) => {
+                let task_name = thread_sink.current_task_name.as_ref().unwrap().clone();
+                if let Some(cache) = cache {
+                    cache.record(&task_name, &subtask_path, &task_change);
+                }
+                if subtask_path.is_empty() {
+                    match &task_change {
+                        TaskChange::TaskStatus(status) => {
+                            summary.set_status(&task_name, status.clone())
+                        }
+                        TaskChange::TaskAttempt(attempt) => {
+                            summary.set_attempt(&task_name, attempt.number)
+                        }
+                        _ => (),
+                    }
+                }
+                view.update(
+                    TaskUpdate {
+                        task_name,
+                        subtask_path,
+                        change: task_change,
+                    },
+                    time,
+                )
+            }
             Change::NameChange(name) => thread_sink.current_task_name = Some(name),
             Change::CloseSink => return false,
         }
@@ -138,8 +861,9 @@ fn send_changes_to_view(thread_sink: &mut ThreadSink, view: &mut dyn View) -> bo
     true
 }
 
+// A change to a task, or one of its subtasks, and when it happened.
 enum Change {
-    TaskChange(TaskChange),
+    TaskChange(Vec<TaskName>, SystemTime, TaskChange),
     NameChange(TaskName),
     CloseSink,
 }
@@ -170,7 +894,31 @@ enum Token {
     Error,
 }
 
+fn write_change(buffer: &mut Vec<u8>, subtask_path: &[TaskName], change: &TaskChange) {
+    buffer.extend_from_slice(TASK_CHANGE_START_TAG.as_bytes());
+    let change = bincode::serialize(&(subtask_path, SystemTime::now(), change)).unwrap();
+    buffer.extend_from_slice(to_hex(&change).as_bytes());
+    buffer.extend_from_slice(TASK_CHANGE_END_TAG.as_bytes());
+}
+
+// Takes what was printed so far out of the buffer, except for the start of a
+// character that is still being printed.
+fn take_text(buffer: &mut Vec<u8>) -> String {
+    let end = match from_utf8(buffer) {
+        Err(error) if error.error_len().is_none() => error.valid_up_to(),
+        _ => buffer.len(),
+    };
+    let rest = buffer.split_off(end);
+    let text = String::from_utf8_lossy(buffer).into_owned();
+    *buffer = rest;
+    text
+}
+
+// What tasks print is sent as messages, but text written straight to the
+// sink is stamped with the time it is read, and no later than the change
+// after it.
 fn get_task_updates(buffer: &[u8]) -> Vec<Change> {
+    let now = SystemTime::now();
     let mut lex = Token::lexer(from_utf8(buffer).unwrap());
     let mut changes: Vec<Change> = Vec::new();
 
@@ -179,12 +927,21 @@ fn get_task_updates(buffer: &[u8]) -> Vec<Change> {
     while let Some(token) = lex.next() {
         match token {
             Token::TaskChangeStart => {
+                let change = parse_task_change(&mut lex);
                 if on_text {
-                    changes.push(Change::TaskChange(TaskChange::TaskMessage(text.clone())));
+                    let printed = match &change {
+                        Change::TaskChange(_, time, _) => (*time).min(now),
+                        _ => now,
+                    };
+                    changes.push(Change::TaskChange(
+                        Vec::new(),
+                        printed,
+                        TaskChange::TaskMessage(text.clone()),
+                    ));
                     text.clear();
                     on_text = false;
                 }
-                changes.push(parse_task_change(&mut lex));
+                changes.push(change);
             }
             Token::NameChangeStart => changes.push(parse_name_change(&mut lex)),
             Token::Text => {
@@ -196,7 +953,11 @@ fn get_task_updates(buffer: &[u8]) -> Vec<Change> {
         }
     }
     if !text.is_empty() {
-        changes.push(Change::TaskChange(TaskChange::TaskMessage(text.clone())));
+        changes.push(Change::TaskChange(
+            Vec::new(),
+            now,
+            TaskChange::TaskMessage(text.clone()),
+        ));
     }
     return changes;
 }
@@ -210,7 +971,21 @@ fn parse_task_change(lex: &mut Lexer<Token>) -> Change {
             _ => panic!(),
         }
     }
-    return Change::TaskChange(bincode::deserialize(text.as_bytes()).unwrap());
+    let (subtask_path, time, task_change) = bincode::deserialize(&from_hex(&text)).unwrap();
+    return Change::TaskChange(subtask_path, time, task_change);
+}
+
+// The serialized changes are sent as hex since they are mixed with the
+// printed text, which has to be valid UTF-8.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+        .collect()
 }
 
 fn parse_name_change(lex: &mut Lexer<Token>) -> Change {
@@ -225,37 +1000,184 @@ fn parse_name_change(lex: &mut Lexer<Token>) -> Change {
     return Change::NameChange(text);
 }
 
-fn run_tasks_in_thread(task_queue: Arc<Mutex<VecDeque<Box<dyn Task>>>>, sink: LocalStream) {
+fn run_tasks_in_thread(
+    task_queue: Arc<TaskQueue>,
+    sink: LocalStream,
+    fail_fast: bool,
+    log_level: Level,
+    cancellation: Arc<Cancellation>,
+    cache: Option<Arc<ResultCache>>,
+) {
     thread::spawn(move || {
-        let logger = ThreadLogger::new(sink.clone());
-        while let Some(task) = get_next_task(&task_queue) {
-            logger.switch_task(task.name());
-            if let Err(_) = spawn_task_thread(task, logger.clone()).join() {
-                logger.set_status(Status::Failed(String::from(
-                    "Aborting task since thread panicked",
-                )));
+        let mut logger =
+            ThreadLogger::new(sink.clone(), task_queue.clone(), log_level, cancellation);
+        while let Some(next) = task_queue.next() {
+            let task = match next {
+                NextTask::Run(task) => task,
+                NextTask::Skip(task) => {
+                    logger.switch_task(task.name());
+                    logger.set_status(Status::Skipped);
+                    continue;
+                }
+            };
+            let task_name = task.name();
+            logger.switch_task(task_name.clone());
+            let cached = cache.as_ref().and_then(|cache| {
+                let dependency_answers = task_queue.answers(&task.dependencies());
+                use_cache(task.as_ref(), dependency_answers, cache, &logger)
+            });
+            if let Some(answer) = cached {
+                task_queue.task_done(task_name, Status::Finished(answer));
+                continue;
+            }
+            let status = run_task(task, &logger);
+            let succeeded = matches!(status, Status::Finished(_));
+            task_queue.task_done(task_name, status);
+            if !succeeded && fail_fast {
+                skip_remaining_tasks(&task_queue, &mut logger);
             }
         }
         logger.close_sink();
     });
 }
 
-fn spawn_task_thread(task: Box<dyn Task>, logger: ThreadLogger) -> JoinHandle<()> {
+// The task is shared with the thread running it so that it can be run again
+// if the attempt fails. A panicking attempt poisons the mutex, which is fine
+// since the task is only borrowed immutably.
+type SharedTask = Arc<Mutex<Box<dyn Task>>>;
+
+// The waiting thread is woken up when the task is done.
+fn spawn_task_thread(task: SharedTask, logger: ThreadLogger) -> JoinHandle<TaskResult> {
+    let waiting = thread::current();
     thread::spawn(move || {
-        set_output_capture(Some(logger.sink.clone()));
-        run_task(task, &logger);
+        set_output_capture(logger.capture.clone());
+        log_bridge::set_task_logger(Box::new(logger.clone()));
+        let task = task.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = task.run(&logger);
+        waiting.unpark();
+        result
     })
 }
 
-fn get_next_task(task_queue: &Arc<Mutex<VecDeque<Box<dyn Task>>>>) -> Option<Box<dyn Task>> {
-    task_queue.lock().unwrap().pop_front()
+// Gives the answer of the task from the cache if it is there. Otherwise the
+// cache is made to record the result of the task, to have it the next time.
+// The result depends on the answers of the tasks it depends on too.
+fn use_cache(
+    task: &dyn Task,
+    dependency_answers: Vec<(TaskName, Answer)>,
+    cache: &ResultCache,
+    logger: &ThreadLogger,
+) -> Option<Answer> {
+    let mut key = task.cache_key()?;
+    key.dependency_answers = dependency_answers;
+    let key = match key.hash(&task.name()) {
+        Ok(key) => key,
+        Err(error) => {
+            logger.warn(&format!("Not cached: {}", error));
+            return None;
+        }
+    };
+    match cache.load(&key) {
+        Some(cached) => {
+            for (subtask_path, change) in cached.log {
+                logger.send_subtask_update(&subtask_path, change);
+            }
+            logger.send_update(TaskChange::TaskCached);
+            logger.set_status(Status::Finished(cached.answer.clone()));
+            Some(cached.answer)
+        }
+        None => {
+            cache.start_recording(task.name(), key);
+            None
+        }
+    }
 }
 
-fn run_task(task: Box<dyn Task>, logger: &ThreadLogger) {
+// Ctrl-C stops the run instead of killing the process, so that the caller
+// still gets a summary of what was done. Once no run is going on any more, it
+// kills the process again.
+fn catch_interrupts(interrupted: &Arc<AtomicBool>) -> SigId {
+    let not_catching = NOT_CATCHING.get_or_init(|| {
+        let not_catching = Arc::new(AtomicBool::new(true));
+        signal_hook::flag::register_conditional_default(SIGINT, not_catching.clone()).unwrap();
+        not_catching
+    });
+    let mut catching_runs = CATCHING_RUNS.lock().unwrap();
+    *catching_runs += 1;
+    not_catching.store(false, Ordering::SeqCst);
+    signal_hook::flag::register(SIGINT, interrupted.clone()).unwrap()
+}
+
+fn stop_catching_interrupts(signal: SigId) {
+    signal_hook::low_level::unregister(signal);
+    let mut catching_runs = CATCHING_RUNS.lock().unwrap();
+    *catching_runs -= 1;
+    if *catching_runs == 0 {
+        NOT_CATCHING.get().unwrap().store(true, Ordering::SeqCst);
+    }
+}
+
+fn skip_remaining_tasks(task_queue: &TaskQueue, logger: &mut ThreadLogger) {
+    for task in task_queue.close() {
+        logger.switch_task(task.name());
+        logger.set_status(Status::Skipped);
+    }
+}
+
+// While the attempt runs, what it prints is sent on regularly, so that it is
+// stamped with about the time it was printed.
+fn run_attempt(task: SharedTask, logger: &ThreadLogger) -> (ThreadId, thread::Result<TaskResult>) {
+    let handle = spawn_task_thread(task, logger.clone());
+    let thread = handle.thread().id();
+    while !handle.is_finished() {
+        thread::park_timeout(CAPTURE_PERIOD);
+        logger.send_captured();
+    }
+    let result = handle.join();
+    logger.send_captured();
+    (thread, result)
+}
+
+fn run_task(task: Box<dyn Task>, logger: &ThreadLogger) -> Status {
+    let retry_policy = task.retry_policy();
+    let task: SharedTask = Arc::new(Mutex::new(task));
     logger.set_status(Status::Running);
-    let result = task.run(logger);
-    match result {
-        Ok(answer) => logger.set_status(Status::Finished(answer)),
-        Err(message) => logger.set_status(Status::Failed(message)),
+    let mut attempt = 1;
+    let mut attempt_threads = Vec::new();
+    let status = loop {
+        let attempt_logger = ThreadLogger {
+            capture: Some(LocalStream::default()),
+            ..logger.with_new_subtasks()
+        };
+        let (thread, result) = run_attempt(task.clone(), &attempt_logger);
+        attempt_threads.push(thread);
+        let (status, retry) = match result {
+            Ok(result) => {
+                let status = attempt_logger.roll_up(result);
+                let retry = matches!(status, Status::Failed(_));
+                (status, retry)
+            }
+            Err(_) => (
+                Status::Failed(String::from("Aborting task since thread panicked")),
+                retry_policy.retry_on_panic,
+            ),
+        };
+        if !retry || attempt >= retry_policy.max_attempts || logger.is_cancelled() {
+            break status;
+        }
+        thread::sleep(retry_policy.delay_after(attempt));
+        attempt += 1;
+        logger.send_update(TaskChange::TaskAttempt(Attempt {
+            number: attempt,
+            max_attempts: retry_policy.max_attempts,
+        }));
     };
+    let succeeded = matches!(status, Status::Finished(_));
+    // Only failed attempts are retried, so it passed after failing.
+    if succeeded && attempt > 1 {
+        logger.send_update(TaskChange::TaskFlaky);
+    }
+    logger.set_status(status.clone());
+    logger.cancellation.forget_process_groups(&attempt_threads);
+    status
 }
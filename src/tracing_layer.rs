@@ -0,0 +1,123 @@
+use super::common::*;
+use super::log_bridge::{add_runner_record, with_task_logger};
+use super::tasks::Subtask;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+thread_local! {
+    // The subtasks of the spans that were opened by the task running on the
+    // current thread.
+    static SPAN_SUBTASKS: RefCell<HashMap<Id, Box<dyn Subtask>>> = RefCell::new(HashMap::new());
+}
+
+// Passes the events of tracing on to the task running on the current thread,
+// like LogBridge does for the log crate. A span opened by a task is shown as
+// a subtask of it, which is finished when the span is closed. Events outside
+// of the tasks end up in the log of the runner.
+pub struct TaskLayer;
+
+impl<S> Layer<S> for TaskLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes, id: &Id, context: Context<S>) {
+        let name = attributes.metadata().name();
+        let ancestor_ids: Vec<Id> = context
+            .span(id)
+            .map(|span| span.scope().skip(1).map(|ancestor| ancestor.id()).collect())
+            .unwrap_or_default();
+        SPAN_SUBTASKS.with(|subtasks| {
+            let mut subtasks = subtasks.borrow_mut();
+            let parent = ancestor_ids.iter().find_map(|id| subtasks.get(id));
+            let subtask = match parent {
+                Some(parent) => Some(parent.subtask(name)),
+                None => with_task_logger(|logger| logger.subtask(name)),
+            };
+            if let Some(subtask) = subtask {
+                subtasks.insert(id.clone(), subtask);
+            }
+        });
+    }
+
+    fn on_event(&self, event: &Event, context: Context<S>) {
+        let mut visitor = FieldVisitor {
+            message: String::new(),
+            fields: Vec::new(),
+        };
+        event.record(&mut visitor);
+        let level = to_level(*event.metadata().level());
+        let fields: Vec<(&str, &str)> = visitor
+            .fields
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let span_ids: Vec<Id> = context
+            .event_scope(event)
+            .map(|scope| scope.map(|span| span.id()).collect())
+            .unwrap_or_default();
+        let logged_by_span = SPAN_SUBTASKS.with(|subtasks| {
+            let subtasks = subtasks.borrow();
+            let subtask = span_ids.iter().find_map(|id| subtasks.get(id))?;
+            subtask.record(level, &visitor.message, &fields);
+            Some(())
+        });
+        let logged = logged_by_span.or_else(|| {
+            with_task_logger(|logger| logger.record(level, &visitor.message, &fields))
+        });
+        if logged.is_none() {
+            add_runner_record(LogRecord {
+                level,
+                message: visitor.message,
+                fields: visitor.fields,
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, _: Context<S>) {
+        let subtask = SPAN_SUBTASKS.with(|subtasks| subtasks.borrow_mut().remove(&id));
+        if let Some(subtask) = subtask {
+            subtask.finish(Ok(None));
+        }
+    }
+}
+
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = String::from(value);
+        } else {
+            self.fields
+                .push((String::from(field.name()), String::from(value)));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .push((String::from(field.name()), format!("{:?}", value)));
+        }
+    }
+}
+
+fn to_level(level: tracing::Level) -> Level {
+    match level {
+        tracing::Level::TRACE => Level::Trace,
+        tracing::Level::DEBUG => Level::Debug,
+        tracing::Level::INFO => Level::Info,
+        tracing::Level::WARN => Level::Warn,
+        tracing::Level::ERROR => Level::Error,
+    }
+}
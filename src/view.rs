@@ -1,8 +1,13 @@
 use super::common::*;
+use super::plan::Plan;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use termion::{clear, color, color::Color, cursor, style};
 
 const MAX_LINES_PER_LOG: usize = 5;
+const PROGRESS_BAR_WIDTH: usize = 20;
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
 const PENDING_TEXT: StatusText = StatusText {
     color: &color::Blue,
     characters: "Pending",
@@ -19,63 +24,342 @@ const FAILED_TEXT: StatusText = StatusText {
     color: &color::Red,
     characters: "Failed",
 };
+const CACHED_TEXT: StatusText = StatusText {
+    color: &color::Magenta,
+    characters: "Cached",
+};
+const SKIPPED_TEXT: StatusText = StatusText {
+    color: &color::Yellow,
+    characters: "Skipped",
+};
 
 pub struct Console {
     logs: Vec<TaskLog>,
+    // What was logged outside of the tasks, shown below them.
+    runner_log: TaskLog,
+    clock: Clock,
+    timestamps: Timestamps,
+    // How long the tasks took the last time they finished, by name.
+    previous_durations: HashMap<TaskName, Duration>,
+}
+
+// How the time that a log line was printed is shown in front of it.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Timestamps {
+    Hidden,
+    // The time since the run started, for example [+12.345s].
+    Relative,
+    // The time of day in UTC, for example [13:45:09.123].
+    Absolute,
+}
+
+// The current time as seen by the view, used to show how long the running
+// tasks have been running and to animate their spinners.
+#[derive(Clone, Copy)]
+struct Clock {
+    now: SystemTime,
+    ticks: usize,
+    run_started: SystemTime,
 }
 
 struct TaskLog {
     name: TaskName,
     status: Status,
     lines: String,
+    // When each of the lines was printed.
+    line_times: Vec<SystemTime>,
+    attempt: Option<Attempt>,
+    // How many levels below a top level task this task is shown.
+    depth: usize,
+    subtasks: Vec<TaskLog>,
+    progress: Option<TaskProgress>,
+    started: Option<SystemTime>,
+    ended: Option<SystemTime>,
+    // Whether the result was taken from the cache instead of running the
+    // task.
+    cached: bool,
+    // How long the task took the last time it finished, shown next to how
+    // long it takes now.
+    previous_duration: Option<Duration>,
+    // Whether the task both failed and passed with the same inputs.
+    flaky: bool,
+}
+
+// The latest progress of a task, together with what is needed to estimate
+// how fast it is going.
+struct TaskProgress {
+    progress: Progress,
+    first_done: f64,
+    first_reported: Instant,
+    last_reported: Instant,
+}
+
+impl TaskProgress {
+    fn new(progress: Progress) -> TaskProgress {
+        let now = Instant::now();
+        TaskProgress {
+            first_done: progress.done(),
+            progress,
+            first_reported: now,
+            last_reported: now,
+        }
+    }
+
+    fn update(&mut self, progress: Progress) {
+        self.progress = progress;
+        self.last_reported = Instant::now();
+    }
+
+    // Units done per second, if anything has been done since the first
+    // report.
+    fn rate(&self) -> Option<f64> {
+        let seconds = (self.last_reported - self.first_reported).as_secs_f64();
+        let done = self.progress.done() - self.first_done;
+        if seconds > 0.0 && done > 0.0 {
+            Some(done / seconds)
+        } else {
+            None
+        }
+    }
 }
 
 impl TaskLog {
-    fn new(name: TaskName) -> TaskLog {
+    fn new(name: TaskName, depth: usize) -> TaskLog {
         TaskLog {
             name,
             status: Status::Pending,
             lines: String::new(),
+            line_times: Vec::new(),
+            attempt: None,
+            depth,
+            subtasks: Vec::new(),
+            progress: None,
+            started: None,
+            ended: None,
+            cached: false,
+            previous_duration: None,
+            flaky: false,
         }
     }
 
-    fn print(&self) {
-        println!("{}", format_status(&self.status, &self.name));
-        print_messages(&self.status, &self.lines);
+    fn print(&self, clock: Clock, timestamps: Timestamps) {
+        let indent = "  ".repeat(self.depth);
+        println!("{}{}", indent, format_status(self, clock));
+        if self.shows_subtasks() {
+            self.subtasks
+                .iter()
+                .for_each(|subtask| subtask.print(clock, timestamps));
+        }
+        print_messages(self, &indent, clock, timestamps);
     }
 
     fn nbr_of_visible_lines(&self) -> usize {
-        match &self.status {
-            Status::Finished(_) => 1,
-            Status::Failed(error) => 1 + get_lines(error) + get_lines(&self.lines),
-            _ => floor(1 + get_lines(&self.lines), MAX_LINES_PER_LOG),
+        let subtask_lines = if self.shows_subtasks() {
+            get_nbr_of_visible_lines(&self.subtasks)
+        } else {
+            0
+        };
+        subtask_lines
+            + match &self.status {
+                Status::Finished(_) => 1,
+                Status::Failed(error) => 1 + get_lines(error) + get_lines(&self.lines),
+                _ => floor(1 + get_lines(&self.lines), MAX_LINES_PER_LOG),
+            }
+    }
+
+    // Like the log, the subtasks are hidden once the task has finished.
+    fn shows_subtasks(&self) -> bool {
+        !matches!(self.status, Status::Finished(_))
+    }
+
+    // Subtasks are added the first time they are mentioned.
+    fn get_subtask_log(&mut self, subtask_path: &[TaskName]) -> &mut TaskLog {
+        match subtask_path.split_first() {
+            None => self,
+            Some((name, subtask_path)) => {
+                let position = match self.subtasks.iter().position(|log| &log.name == name) {
+                    Some(position) => position,
+                    None => {
+                        let subtask = TaskLog::new(name.clone(), self.depth + 1);
+                        self.subtasks.push(subtask);
+                        self.subtasks.len() - 1
+                    }
+                };
+                self.subtasks[position].get_subtask_log(subtask_path)
+            }
         }
     }
 
-    fn add_message(&mut self, message: LogMessage) {
-        self.lines.push_str(message.as_str());
+    // A message can end in the middle of a line, which is then continued by
+    // the next message. The line keeps the time of its first part.
+    fn add_message(&mut self, message: &str, time: SystemTime) {
+        for part in message.split_inclusive('\n') {
+            if self.lines.is_empty() || self.lines.ends_with('\n') {
+                self.line_times.push(time);
+            }
+            self.lines.push_str(part);
+        }
     }
 
-    fn set_status(&mut self, status: Status) {
+    // A task that is set back to pending is about to run again, so what it
+    // did before is cleared.
+    fn set_status(&mut self, status: Status, time: SystemTime) {
+        match status {
+            Status::Pending => {
+                *self = TaskLog {
+                    previous_duration: self.previous_duration,
+                    ..TaskLog::new(self.name.clone(), self.depth)
+                }
+            }
+            Status::Running => self.started = self.started.or(Some(time)),
+            Status::Finished(_) | Status::Failed(_) => self.ended = Some(time),
+            _ => (),
+        }
         self.status = status;
     }
+
+    fn is_running(&self) -> bool {
+        self.status == Status::Running || self.subtasks.iter().any(|log| log.is_running())
+    }
+
+    // How long the task has been running, or how long it ran once it has
+    // ended.
+    fn running_time(&self, clock: Clock) -> Option<Duration> {
+        let started = self.started?;
+        let ended = self.ended.unwrap_or(clock.now);
+        Some(ended.duration_since(started).unwrap_or_default())
+    }
+
+    fn set_progress(&mut self, progress: Progress) {
+        match &mut self.progress {
+            Some(task_progress) => task_progress.update(progress),
+            None => self.progress = Some(TaskProgress::new(progress)),
+        }
+    }
+
+    // The log of the previous attempts is kept, separated from the log of
+    // the new attempt.
+    fn set_attempt(&mut self, attempt: Attempt, time: SystemTime) {
+        if !self.lines.is_empty() && !self.lines.ends_with('\n') {
+            self.lines.push('\n');
+        }
+        self.add_message(
+            &format!(
+                "--- attempt {}/{} ---\n",
+                attempt.number, attempt.max_attempts
+            ),
+            time,
+        );
+        self.attempt = Some(attempt);
+        self.progress = None;
+    }
 }
 
 fn get_lines(lines: &str) -> usize {
     lines.split_terminator('\n').count()
 }
 
-fn format_status(status: &Status, task_name: &TaskName) -> String {
-    match status {
+fn format_status(log: &TaskLog, clock: Clock) -> String {
+    let task_name = &format_timed_name(log, clock);
+    match &log.status {
         Status::Pending => format_status_line(PENDING_TEXT, task_name),
-        Status::Running => format_status_line(RUNNING_TEXT, task_name),
-        Status::Finished(result) => match result {
-            Some(answer) => {
-                format_detailed_status_line(FINISHED_TEXT, task_name, format!(": {}", answer))
+        Status::Running => {
+            format_detailed_status_line(RUNNING_TEXT, task_name, format_running_details(log))
+        }
+        Status::Finished(result) => {
+            let status_text = if log.cached {
+                CACHED_TEXT
+            } else {
+                FINISHED_TEXT
+            };
+            match result {
+                Some(answer) => {
+                    format_detailed_status_line(status_text, task_name, format!(": {}", answer))
+                }
+                None => format_status_line(status_text, task_name),
             }
-            None => format_status_line(FINISHED_TEXT, task_name),
-        },
+        }
         Status::Failed(_) => format_status_line(FAILED_TEXT, task_name),
+        Status::Skipped => format_status_line(SKIPPED_TEXT, task_name),
+    }
+}
+
+// For example: / 12.3s (last 10.1s) download happy-cow.pdf (flaky)
+fn format_timed_name(log: &TaskLog, clock: Clock) -> String {
+    let running_time = match (log.running_time(clock), log.previous_duration) {
+        (Some(running_time), Some(previous)) => format!(
+            "{} {}(last {}){} ",
+            format_running_time(running_time),
+            style::Faint,
+            format_running_time(previous),
+            style::Reset
+        ),
+        (Some(running_time), None) => format!("{} ", format_running_time(running_time)),
+        (None, _) => String::new(),
+    };
+    let spinner = if log.status == Status::Running {
+        format!("{} ", SPINNER_FRAMES[clock.ticks % SPINNER_FRAMES.len()])
+    } else {
+        String::new()
+    };
+    let flaky = if log.flaky {
+        format!(" {}(flaky){}", color::Fg(color::Yellow), style::Reset)
+    } else {
+        String::new()
+    };
+    format!("{}{}{}{}", spinner, running_time, log.name, flaky)
+}
+
+fn format_running_time(running_time: Duration) -> String {
+    if running_time.as_secs() < 60 {
+        format!("{:.1}s", running_time.as_secs_f64())
+    } else {
+        format_duration(running_time.as_secs_f64())
+    }
+}
+
+fn format_running_details(log: &TaskLog) -> String {
+    let mut details = String::new();
+    if let Some(attempt) = &log.attempt {
+        details.push_str(&format!(
+            " (attempt {}/{})",
+            attempt.number, attempt.max_attempts
+        ));
+    }
+    if let Some(progress) = &log.progress {
+        details.push_str(&format_progress(progress));
+    }
+    details
+}
+
+// For example: [#########-----------] 45% 1.2 MB/s ETA 3s
+fn format_progress(task_progress: &TaskProgress) -> String {
+    let progress = &task_progress.progress;
+    let filled = (progress.fraction() * PROGRESS_BAR_WIDTH as f64).round() as usize;
+    let mut text = format!(
+        " [{}{}] {:.0}%",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        progress.fraction() * 100.0
+    );
+    if let Some(rate) = task_progress.rate() {
+        if let Progress::Count { unit, .. } = progress {
+            text.push_str(&format!(" {:.1} {}/s", rate, unit));
+        }
+        let remaining = (progress.total() - progress.done()).max(0.0) / rate;
+        text.push_str(&format!(" ETA {}", format_duration(remaining)));
+    }
+    text
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
     }
 }
 
@@ -104,30 +388,96 @@ struct StatusText {
     characters: &'static str,
 }
 
-fn print_messages(status: &Status, messages: &str) {
-    match status {
+fn print_messages(log: &TaskLog, indent: &str, clock: Clock, timestamps: Timestamps) {
+    let messages: Vec<String> = log
+        .lines
+        .split_terminator('\n')
+        .zip(log.line_times.iter())
+        .map(|(line, time)| format!("{}{}", format_timestamp(*time, clock, timestamps), line))
+        .collect();
+    match &log.status {
         Status::Finished(_) => (),
         Status::Failed(error) => {
-            for message in messages.split_terminator('\n') {
-                println!("  {}", message);
+            for message in messages {
+                println!("{}  {}", indent, message);
             }
-            println!("  {}", error);
+            println!("{}  {}", indent, error);
         }
         _ => {
-            if messages.is_empty() {
-                return;
-            }
-            let most_recent_messages: Vec<&str> = messages
-                .rsplit_terminator('\n')
-                .take(MAX_LINES_PER_LOG - 1)
-                .collect();
-            for message in most_recent_messages.iter().rev() {
-                println!("  {}", message);
+            let first_visible = messages.len().saturating_sub(MAX_LINES_PER_LOG - 1);
+            for message in &messages[first_visible..] {
+                println!("{}  {}", indent, message);
             }
         }
     }
 }
 
+// For example: WARN disk almost full free=2 GB
+fn format_record(record: &LogRecord) -> String {
+    let level_style = match record.level {
+        Level::Trace | Level::Debug => format!("{}", style::Faint),
+        Level::Info => String::new(),
+        Level::Warn => format!("{}", color::Fg(color::Yellow)),
+        Level::Error => format!("{}", color::Fg(color::Red)),
+    };
+    let fields: String = record
+        .fields
+        .iter()
+        .map(|(key, value)| format!(" {}={}", key, value))
+        .collect();
+    format!(
+        "{}{} {}{}{}\n",
+        level_style,
+        record.level.name(),
+        record.message,
+        fields,
+        style::Reset
+    )
+}
+
+// Every line is styled on its own, since lines are printed one by one.
+fn format_stderr(message: &str) -> String {
+    message
+        .split_inclusive('\n')
+        .map(|part| {
+            let line = part.trim_end_matches('\n');
+            let end = &part[line.len()..];
+            format!(
+                "{}{}{}{}{}",
+                style::Faint,
+                color::Fg(color::Red),
+                line,
+                style::Reset,
+                end
+            )
+        })
+        .collect()
+}
+
+fn format_timestamp(time: SystemTime, clock: Clock, timestamps: Timestamps) -> String {
+    match timestamps {
+        Timestamps::Hidden => String::new(),
+        Timestamps::Relative => format_since_start(time, clock.run_started),
+        Timestamps::Absolute => {
+            let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let seconds_of_day = since_epoch.as_secs() % (24 * 60 * 60);
+            format!(
+                "[{:02}:{:02}:{:02}.{:03}] ",
+                seconds_of_day / 3600,
+                seconds_of_day % 3600 / 60,
+                seconds_of_day % 60,
+                since_epoch.subsec_millis()
+            )
+        }
+    }
+}
+
+// For example [+12.345s] followed by a space.
+pub(crate) fn format_since_start(time: SystemTime, run_started: SystemTime) -> String {
+    let since_start = time.duration_since(run_started).unwrap_or_default();
+    format!("[+{:.3}s] ", since_start.as_secs_f64())
+}
+
 fn floor(x: usize, y: usize) -> usize {
     if x < y {
         x
@@ -138,7 +488,55 @@ fn floor(x: usize, y: usize) -> usize {
 
 impl Console {
     pub fn new() -> Console {
-        Console { logs: Vec::new() }
+        Console::with_timestamps(Timestamps::Hidden)
+    }
+
+    pub fn with_timestamps(timestamps: Timestamps) -> Console {
+        Console {
+            logs: Vec::new(),
+            runner_log: TaskLog::new(String::from("runner"), 0),
+            clock: Clock {
+                now: SystemTime::now(),
+                ticks: 0,
+                run_started: SystemTime::now(),
+            },
+            timestamps,
+            previous_durations: HashMap::new(),
+        }
+    }
+
+    // Shows how long the tasks took the last time, for example from the
+    // history of the runs.
+    pub fn set_previous_durations(&mut self, durations: HashMap<TaskName, Duration>) {
+        self.previous_durations = durations;
+    }
+
+    fn new_task_log(&self, name: TaskName, depth: usize) -> TaskLog {
+        let previous_duration = match depth {
+            0 => self.previous_durations.get(&name).copied(),
+            _ => None,
+        };
+        TaskLog {
+            previous_duration,
+            ..TaskLog::new(name, depth)
+        }
+    }
+
+    fn print(&self) {
+        print_logs(&self.logs, self.clock, self.timestamps);
+        if !self.runner_log.lines.is_empty() {
+            println!("{}{}{}", style::Bold, self.runner_log.name, style::Reset);
+            print_messages(&self.runner_log, "", self.clock, self.timestamps);
+        }
+    }
+
+    fn clear(&self) {
+        let runner_lines = if self.runner_log.lines.is_empty() {
+            0
+        } else {
+            self.runner_log.nbr_of_visible_lines()
+        };
+        clear_lines(get_nbr_of_visible_lines(&self.logs) + runner_lines);
     }
 }
 
@@ -146,19 +544,83 @@ impl View for Console {
     fn initialize(&mut self, tasks: Vec<TaskName>) {
         self.logs = tasks
             .into_iter()
-            .map(|task_name| TaskLog::new(task_name))
+            .map(|task_name| self.new_task_log(task_name, 0))
             .collect();
-        print_logs(&self.logs);
+        self.clock.run_started = SystemTime::now();
+        self.print();
     }
 
-    fn update(&mut self, task_update: TaskUpdate) {
-        clear_lines(get_nbr_of_visible_lines(&self.logs));
-        let log = get_matching_log(task_update.task_name, &mut self.logs);
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        self.clear();
+        self.clock.now = SystemTime::now();
+        let log = get_matching_log(task_update.task_name, &mut self.logs)
+            .get_subtask_log(&task_update.subtask_path);
         match task_update.change {
-            TaskChange::TaskMessage(message) => log.add_message(message),
-            TaskChange::TaskStatus(status) => log.set_status(status),
+            TaskChange::TaskMessage(message) => log.add_message(&message, time),
+            TaskChange::TaskStatus(status) => log.set_status(status, time),
+            TaskChange::TaskAttempt(attempt) => log.set_attempt(attempt, time),
+            TaskChange::TaskProgress(progress) => log.set_progress(progress),
+            TaskChange::TaskRecord(record) => log.add_message(&format_record(&record), time),
+            TaskChange::TaskStderr(message) => log.add_message(&format_stderr(&message), time),
+            TaskChange::TaskCached => log.cached = true,
+            TaskChange::TaskFlaky => log.flaky = true,
+        }
+        self.print();
+    }
+
+    fn add_task(&mut self, task: TaskName, parent: Option<TaskName>) {
+        self.clear();
+        let parent = parent.and_then(|parent| self.logs.iter().position(|log| log.name == parent));
+        match parent {
+            Some(parent) => {
+                let depth = self.logs[parent].depth + 1;
+                let position = self.logs[parent + 1..]
+                    .iter()
+                    .position(|log| log.depth < depth)
+                    .map_or(self.logs.len(), |offset| parent + 1 + offset);
+                let log = self.new_task_log(task, depth);
+                self.logs.insert(position, log);
+            }
+            None => {
+                let log = self.new_task_log(task, 0);
+                self.logs.push(log);
+            }
+        }
+        self.print();
+    }
+
+    // The tasks it added are removed with it.
+    fn remove_task(&mut self, task: TaskName) {
+        self.clear();
+        if let Some(position) = self.logs.iter().position(|log| log.name == task) {
+            let depth = self.logs[position].depth;
+            let end = self.logs[position + 1..]
+                .iter()
+                .position(|log| log.depth <= depth)
+                .map_or(self.logs.len(), |offset| position + 1 + offset);
+            self.logs.drain(position..end);
+        }
+        self.print();
+    }
+
+    fn runner_record(&mut self, record: LogRecord, time: SystemTime) {
+        self.clear();
+        self.runner_log.add_message(&format_record(&record), time);
+        self.print();
+    }
+
+    fn plan(&mut self, plan: &Plan) {
+        println!("{}", plan);
+    }
+
+    fn refresh(&mut self, now: SystemTime) {
+        if !self.logs.iter().any(|log| log.is_running()) {
+            return;
         }
-        print_logs(&self.logs);
+        self.clear();
+        self.clock.now = now;
+        self.clock.ticks += 1;
+        self.print();
     }
 }
 
@@ -178,6 +640,6 @@ fn get_matching_log(task_name: TaskName, logs: &mut Vec<TaskLog>) -> &mut TaskLo
     logs.iter_mut().find(|log| log.name == task_name).unwrap()
 }
 
-fn print_logs(logs: &Vec<TaskLog>) {
-    logs.iter().for_each(|log| log.print());
+fn print_logs(logs: &Vec<TaskLog>, clock: Clock, timestamps: Timestamps) {
+    logs.iter().for_each(|log| log.print(clock, timestamps));
 }
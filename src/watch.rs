@@ -0,0 +1,143 @@
+use super::common::*;
+use super::tasks::Task;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+// Watches the inputs of the tasks, and tells which tasks have to run again
+// once some of them have changed. Changes are debounced, so that saving many
+// files at once only runs the tasks again once.
+pub struct InputWatcher {
+    // Stops watching when dropped.
+    debouncer: Debouncer<RecommendedWatcher>,
+    events: Receiver<DebounceEventResult>,
+    debounce: Duration,
+    tasks: Vec<WatchedTask>,
+    // Canonical, like the inputs.
+    task_file: Option<PathBuf>,
+}
+
+// What changed while waiting.
+#[derive(PartialEq, Debug, Default)]
+pub struct Changes {
+    // The tasks with changed inputs together with the tasks that depend on
+    // them, since their results are out of date too, in the order the tasks
+    // were given.
+    pub tasks: Vec<TaskName>,
+    // Whether the task file changed, which can change any of the tasks.
+    pub task_file: bool,
+}
+
+struct WatchedTask {
+    name: TaskName,
+    // Canonical, to compare with the paths of the changes.
+    inputs: Vec<PathBuf>,
+    dependencies: Vec<TaskName>,
+}
+
+impl InputWatcher {
+    pub fn new(tasks: &[Box<dyn Task>], debounce: Duration) -> Result<InputWatcher, String> {
+        let (sender, events) = channel();
+        let mut debouncer = new_debouncer(debounce, sender)
+            .map_err(|error| format!("Could not watch the inputs: {}", error))?;
+        let mut watched_tasks = Vec::new();
+        for task in tasks {
+            let mut inputs = Vec::new();
+            for input in task.inputs() {
+                let cannot_watch =
+                    |error: String| format!("Could not watch {}: {}", input.display(), error);
+                let input = input
+                    .canonicalize()
+                    .map_err(|error| cannot_watch(error.to_string()))?;
+                debouncer
+                    .watcher()
+                    .watch(&input, RecursiveMode::Recursive)
+                    .map_err(|error| cannot_watch(error.to_string()))?;
+                inputs.push(input);
+            }
+            watched_tasks.push(WatchedTask {
+                name: task.name(),
+                inputs,
+                dependencies: task.dependencies(),
+            });
+        }
+        Ok(InputWatcher {
+            debouncer,
+            events,
+            debounce,
+            tasks: watched_tasks,
+            task_file: None,
+        })
+    }
+
+    // Also watches the task file that the tasks were loaded from.
+    pub fn watch_task_file(&mut self, task_file: &Path) -> Result<(), String> {
+        let cannot_watch =
+            |error: String| format!("Could not watch {}: {}", task_file.display(), error);
+        let task_file = task_file
+            .canonicalize()
+            .map_err(|error| cannot_watch(error.to_string()))?;
+        // Editors often replace a file instead of writing to it, which a watch
+        // of the file itself would not survive, so its directory is watched.
+        let directory = task_file.parent().unwrap_or(&task_file);
+        self.debouncer
+            .watcher()
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(|error| cannot_watch(error.to_string()))?;
+        self.task_file = Some(task_file);
+        Ok(())
+    }
+
+    // Waits at most the timeout for inputs to change.
+    pub fn changes(&self, timeout: Duration) -> Changes {
+        // The changes to a file can come in more than one batch, for example
+        // when it is created and then written to.
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut wait = timeout;
+        while let Ok(events) = self.events.recv_timeout(wait) {
+            paths.extend(events.into_iter().flatten().map(|event| event.path));
+            wait = self.debounce;
+        }
+        let mut changed: Vec<bool> = self
+            .tasks
+            .iter()
+            .map(|task| {
+                task.inputs
+                    .iter()
+                    .any(|input| paths.iter().any(|path| path.starts_with(input)))
+            })
+            .collect();
+        let mut found_more = true;
+        while found_more {
+            found_more = false;
+            for (position, task) in self.tasks.iter().enumerate() {
+                if !changed[position] && self.depends_on_changed(task, &changed) {
+                    changed[position] = true;
+                    found_more = true;
+                }
+            }
+        }
+        Changes {
+            tasks: self
+                .tasks
+                .iter()
+                .zip(changed)
+                .filter(|(_, changed)| *changed)
+                .map(|(task, _)| task.name.clone())
+                .collect(),
+            task_file: self
+                .task_file
+                .as_ref()
+                .is_some_and(|task_file| paths.contains(task_file)),
+        }
+    }
+
+    fn depends_on_changed(&self, task: &WatchedTask, changed: &[bool]) -> bool {
+        self.tasks
+            .iter()
+            .zip(changed)
+            .any(|(other, changed)| *changed && task.dependencies.contains(&other.name))
+    }
+}
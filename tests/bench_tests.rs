@@ -0,0 +1,109 @@
+mod common;
+
+use common::*;
+use console_runner::{bench::*, common::*, tasks::*};
+use spectral::prelude::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const BENCHMARK: Benchmark = Benchmark {
+    warmup: 2,
+    iterations: 3,
+};
+
+#[test]
+fn every_iteration_of_a_benchmark_is_timed() {
+    let results = BenchmarkResults::default();
+    let task = CountingTask {
+        runs: AtomicU32::new(0),
+        changing_answer: false,
+    };
+
+    let summary = TASK_RUNNER.run(
+        BENCHMARK.wrap(vec![Box::new(task)], &results),
+        &mut NoView {},
+    );
+
+    assert_that(&summary.tasks[0].1).is_equal_to(Status::Finished(Some(String::from("42"))));
+    let results = results.lock().unwrap();
+    assert_that(&*results).has_length(1);
+    assert_that(&results[0].timings).has_length(3);
+}
+
+#[test]
+fn a_benchmark_fails_when_the_answer_changes() {
+    let results = BenchmarkResults::default();
+    let task = CountingTask {
+        runs: AtomicU32::new(0),
+        changing_answer: true,
+    };
+
+    let summary = TASK_RUNNER.run(
+        BENCHMARK.wrap(vec![Box::new(task)], &results),
+        &mut NoView {},
+    );
+
+    assert_that(&summary.tasks[0].1).is_equal_to(Status::Failed(String::from(
+        "Warmup 2 of 2 answered \"2\" instead of \"1\"",
+    )));
+    assert_that(&*results.lock().unwrap()).is_empty();
+}
+
+#[test]
+fn the_statistics_are_of_the_timed_iterations() {
+    let result = BenchmarkResult {
+        task_name: String::from("day-1"),
+        answer: None,
+        timings: vec![4.0, 1.0, 3.0, 2.0],
+    };
+
+    let statistics = result.statistics().unwrap();
+
+    assert_that(&statistics.min).is_equal_to(1.0);
+    assert_that(&statistics.median).is_equal_to(2.5);
+    assert_that(&statistics.mean).is_equal_to(2.5);
+    assert_that(&(statistics.stddev * 1000.0).round()).is_equal_to(1291.0);
+    assert_that(&format_seconds(0.01234)).is_equal_to(String::from("12.3ms"));
+}
+
+#[test]
+fn a_benchmark_without_iterations_has_no_statistics() {
+    let results = BenchmarkResults::default();
+    let task = CountingTask {
+        runs: AtomicU32::new(0),
+        changing_answer: false,
+    };
+    let benchmark = Benchmark {
+        warmup: 1,
+        iterations: 0,
+    };
+
+    let summary = TASK_RUNNER.run(
+        benchmark.wrap(vec![Box::new(task)], &results),
+        &mut NoView {},
+    );
+
+    assert_that(&summary.tasks[0].1).is_equal_to(Status::Finished(Some(String::from("42"))));
+    let results = results.lock().unwrap();
+    assert_that(&results[0].statistics()).is_none();
+    assert_that(&results[0].to_string()).is_equal_to(String::from("counting: no iterations"));
+}
+
+struct CountingTask {
+    runs: AtomicU32,
+    changing_answer: bool,
+}
+
+impl Task for CountingTask {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        let runs = self.runs.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.changing_answer {
+            Ok(Some(runs.to_string()))
+        } else {
+            Ok(Some(String::from("42")))
+        }
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("counting")
+    }
+}
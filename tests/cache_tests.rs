@@ -0,0 +1,75 @@
+mod common;
+
+use common::*;
+use console_runner::cache::*;
+use spectral::prelude::*;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::time::SystemTime;
+
+#[test]
+fn the_hash_of_a_cache_key_changes_with_the_content_of_its_files() {
+    let directory = a_temp_path("hash");
+    fs::create_dir_all(directory.join("src")).unwrap();
+    fs::write(directory.join("src").join("main.rs"), "fn main() {}").unwrap();
+    let key = CacheKey {
+        files: vec![directory.join("src")],
+        params: vec![(String::from("profile"), String::from("dev"))],
+        version: String::from("1"),
+        dependency_answers: vec![(String::from("generate"), Some(String::from("3 files")))],
+    };
+    let hash = key.hash("build").unwrap();
+
+    assert_that(&key.hash("build").unwrap()).is_equal_to(&hash);
+    assert_that(&key.hash("test").unwrap()).is_not_equal_to(&hash);
+    let new_version = CacheKey {
+        version: String::from("2"),
+        ..key.clone()
+    };
+    assert_that(&new_version.hash("build").unwrap()).is_not_equal_to(&hash);
+    let new_answer = CacheKey {
+        dependency_answers: vec![(String::from("generate"), None)],
+        ..key.clone()
+    };
+    assert_that(&new_answer.hash("build").unwrap()).is_not_equal_to(&hash);
+    fs::write(directory.join("src").join("lib.rs"), "").unwrap();
+    assert_that(&key.hash("build").unwrap()).is_not_equal_to(&hash);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn a_symbolic_link_is_hashed_by_where_it_points_to() {
+    let directory = a_temp_path("symlink");
+    fs::create_dir_all(directory.join("src")).unwrap();
+    symlink("..", directory.join("src").join("parent")).unwrap();
+    let key = CacheKey {
+        files: vec![directory.join("src")],
+        ..CacheKey::default()
+    };
+    let hash = key.hash("build").unwrap();
+
+    fs::remove_file(directory.join("src").join("parent")).unwrap();
+    symlink(".", directory.join("src").join("parent")).unwrap();
+    assert_that(&key.hash("build").unwrap()).is_not_equal_to(&hash);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn cached_results_can_be_listed_and_cleared() {
+    let cache = ResultCache::new(a_temp_path("results"));
+    assert_that(&cache.entries().unwrap()).is_empty();
+    let result = CachedResult {
+        task_name: String::from("build"),
+        answer: Some(String::from("42")),
+        log: Vec::new(),
+        stored: SystemTime::now(),
+    };
+
+    cache.store("abc123", &result).unwrap();
+
+    assert_that(&cache.load("abc123").as_ref()).is_equal_to(Some(&result));
+    assert_that(&cache.entries().unwrap()).has_length(1);
+    assert_that(&cache.clear().unwrap()).is_equal_to(1);
+    assert_that(&cache.entries().unwrap()).is_empty();
+    fs::remove_dir_all(cache.directory()).unwrap();
+}
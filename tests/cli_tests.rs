@@ -0,0 +1,113 @@
+use console_runner::{
+    bench::Benchmark, cli::*, common::Level, selection::*, summary::ExitPolicy, view::Timestamps,
+};
+use spectral::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[test]
+fn the_options_are_parsed_from_the_arguments() {
+    let options = parse_args(&args(&[
+        "--jobs",
+        "4",
+        "-o",
+        "json",
+        "--timestamps",
+        "relative",
+        "--log-level",
+        "debug",
+        "--filter",
+        "build-*",
+        "--filter",
+        "tag:ci",
+        "--exclude",
+        "re:slow",
+        "--fail-fast",
+        "--timeout",
+        "1.5m",
+        "--strict",
+        "--cache-dir",
+        "build/cache",
+        "--no-history",
+        "--repeat",
+        "3",
+        "tasks.txt",
+    ]));
+
+    assert_that(&options).is_equal_to(Ok(Options {
+        task_file: Some(PathBuf::from("tasks.txt")),
+        jobs: Some(4),
+        output: Output::Json,
+        timestamps: Timestamps::Relative,
+        log_level: Level::Debug,
+        selection: Selection {
+            include: vec![
+                Pattern::parse("build-*").unwrap(),
+                Pattern::Tag(String::from("ci")),
+            ],
+            exclude: vec![Pattern::parse("re:slow").unwrap()],
+            show_excluded: false,
+        },
+        fail_fast: true,
+        timeout: Some(Duration::from_secs(90)),
+        exit_policy: ExitPolicy::Strict,
+        dry_run: false,
+        dot: false,
+        watch: false,
+        cache_dir: Some(PathBuf::from("build/cache")),
+        cache_command: None,
+        record: None,
+        replay: None,
+        speed: 1.0,
+        history: None,
+        repeat: 3,
+        bench: None,
+        regressions: None,
+        help: false,
+    }));
+}
+
+#[test]
+fn invalid_arguments_are_rejected() {
+    assert_that(&parse_args(&args(&["--jobs", "0"])))
+        .is_equal_to(Err(String::from("Invalid number of jobs: 0")));
+    assert_that(&parse_args(&args(&["--output", "html"])))
+        .is_equal_to(Err(String::from("Unknown output: html")));
+    assert_that(&parse_args(&args(&["--timestamps", "local"])))
+        .is_equal_to(Err(String::from("Unknown timestamps: local")));
+    assert_that(&parse_args(&args(&["--log-level", "verbose"])))
+        .is_equal_to(Err(String::from("Unknown log level: verbose")));
+    assert_that(&parse_args(&args(&["--timeout"])))
+        .is_equal_to(Err(String::from("--timeout needs a value")));
+    assert_that(&parse_args(&args(&["--timeout", "5 days"])))
+        .is_equal_to(Err(String::from("Invalid time: 5 days")));
+    assert_that(&parse_args(&args(&["--filter", "re:("]))).is_err();
+    assert_that(&parse_args(&args(&["--regressions", "none"])))
+        .is_equal_to(Err(String::from("Invalid number of runs: none")));
+    assert_that(&parse_args(&args(&["--watch", "--repeat", "2"])))
+        .is_equal_to(Err(String::from("--repeat cannot be used with --watch")));
+    assert_that(&parse_args(&args(&["-o", "junit", "--repeat", "2"])))
+        .is_equal_to(Err(String::from("--repeat cannot be used with -o junit")));
+    assert_that(&parse_args(&args(&["--iterations", "0"])))
+        .is_equal_to(Err(String::from("Invalid number of iterations: 0")));
+}
+
+#[test]
+fn the_warmup_and_iterations_are_for_a_benchmark() {
+    let options = parse_args(&args(&["--warmup", "0", "--iterations", "5", "--bench"])).unwrap();
+
+    assert_that(&options.bench).is_equal_to(Some(Benchmark {
+        warmup: 0,
+        iterations: 5,
+    }));
+    assert_that(&parse_args(&args(&["--bench"])).unwrap().bench)
+        .is_equal_to(Some(Benchmark::DEFAULT));
+    assert_that(&parse_args(&args(&["--iterations", "5"])))
+        .is_equal_to(Err(String::from("--iterations needs --bench")));
+    assert_that(&parse_args(&args(&["--bench", "--jobs", "2"])))
+        .is_equal_to(Err(String::from("--jobs cannot be used with --bench")));
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| String::from(*arg)).collect()
+}
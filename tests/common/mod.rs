@@ -0,0 +1,111 @@
+// Shared by the test files. Every test file is a crate of its own that uses
+// only some of this.
+#![allow(dead_code)]
+
+use console_runner::{common::*, selection::*, tasks::*};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+pub const TASK_RUNNER: TaskRunner = TaskRunner {
+    thread_count: 1,
+    view_update_period: 0,
+    timeout: None,
+    fail_fast: false,
+    log_level: Level::Info,
+    grace_period: Duration::ZERO,
+    selection: Selection::ALL,
+    cache_dir: None,
+};
+
+// A path in the temporary directory that no other test process uses.
+pub fn a_temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("console-runner-{}-{}", name, process::id()))
+}
+
+pub fn a_status(name: &str, status: Status) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskStatus(status),
+    }
+}
+
+pub fn a_message(name: &str, message: &str) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskMessage(String::from(message)),
+    }
+}
+
+pub fn a_record(name: &str, record: LogRecord) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskRecord(record),
+    }
+}
+
+pub struct SimpleTask<'a> {
+    pub name: &'a str,
+    pub run_task: fn() -> TaskResult,
+}
+
+impl<'a> Task for SimpleTask<'a> {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        let run_task = self.run_task;
+        run_task()
+    }
+
+    fn name(&self) -> TaskName {
+        String::from(self.name)
+    }
+}
+
+pub struct StoreToMemory {
+    pub tasks: Vec<TaskName>,
+    pub task_updates: Vec<TaskUpdate>,
+    pub update_times: Vec<SystemTime>,
+    pub added_tasks: Vec<(TaskName, Option<TaskName>)>,
+    pub runner_records: Vec<LogRecord>,
+}
+
+impl StoreToMemory {
+    pub fn new() -> StoreToMemory {
+        StoreToMemory {
+            tasks: Vec::new(),
+            task_updates: Vec::new(),
+            update_times: Vec::new(),
+            added_tasks: Vec::new(),
+            runner_records: Vec::new(),
+        }
+    }
+}
+
+impl View for StoreToMemory {
+    fn initialize(&mut self, tasks: Vec<TaskName>) {
+        self.tasks = tasks;
+    }
+
+    fn update(&mut self, task_update: TaskUpdate, time: SystemTime) {
+        self.task_updates.push(task_update);
+        self.update_times.push(time);
+    }
+
+    fn add_task(&mut self, task: TaskName, parent: Option<TaskName>) {
+        self.added_tasks.push((task, parent));
+    }
+
+    fn runner_record(&mut self, record: LogRecord, _: SystemTime) {
+        self.runner_records.push(record);
+    }
+}
+
+pub struct NoView {}
+
+impl View for NoView {
+    fn initialize(&mut self, _: Vec<TaskName>) {}
+
+    fn update(&mut self, _: TaskUpdate, _: SystemTime) {}
+}
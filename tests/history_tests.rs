@@ -0,0 +1,201 @@
+mod common;
+
+use common::*;
+use console_runner::{common::*, history::*, tasks::*};
+use spectral::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn every_run_is_added_to_the_history() {
+    let path = a_temp_path("history-runs");
+    for _ in 0..2 {
+        let history = History::new(path.clone());
+        let mut view = HistoryRecorder::new(history, HashMap::new(), Box::new(NoView {}));
+        TASK_RUNNER.run(vec![Box::new(AnsweringTask {})], &mut view);
+    }
+
+    let runs = History::new(path.clone()).runs().unwrap();
+
+    assert_that(&runs).has_length(2);
+    let task = &runs[1].tasks[0];
+    assert_that(&task.name).is_equal_to(String::from("answering"));
+    assert_that(&task.status).is_equal_to(Status::Finished(Some(String::from("42"))));
+    assert_that(&task.duration).is_some();
+    assert_that(&History::new(path.clone()).previous_durations().unwrap())
+        .contains_key(String::from("answering"));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn tasks_that_became_slower_or_started_to_fail_are_regressions() {
+    let runs = vec![
+        a_run(vec![
+            finished("build", 10),
+            finished("test", 5),
+            failed("lint"),
+        ]),
+        a_run(vec![
+            finished("build", 12),
+            finished("test", 6),
+            failed("lint"),
+        ]),
+        a_run(vec![finished("build", 11), failed("test"), failed("lint")]),
+        a_run(vec![finished("build", 20), failed("test"), failed("lint")]),
+    ];
+
+    assert_that(&find_regressions(&runs, 2)).is_equal_to(vec![Regression::Slower {
+        task: String::from("build"),
+        duration: Duration::from_secs(20),
+        usual: Duration::from_secs(12),
+    }]);
+    assert_that(&find_regressions(&runs[..3], 2)).is_equal_to(vec![Regression::Failing {
+        task: String::from("test"),
+        error: String::from("Broken"),
+    }]);
+}
+
+#[test]
+fn runs_that_were_stopped_are_not_compared() {
+    let interrupted = RunRecord {
+        interrupted: true,
+        ..a_run(vec![failed("build")])
+    };
+    let runs = vec![
+        a_run(vec![finished("build", 10)]),
+        interrupted.clone(),
+        a_run(vec![finished("build", 10)]),
+        interrupted,
+    ];
+
+    assert_that(&find_regressions(&runs, 1)).is_empty();
+}
+
+#[test]
+fn a_line_of_the_history_that_cannot_be_read_is_skipped() {
+    let path = a_temp_path("history-broken");
+    let history = History::new(path.clone());
+    history.append(&a_run(vec![finished("build", 10)])).unwrap();
+    let cut_off = fs::read_to_string(&path).unwrap() + "{\"started\":\n";
+    fs::write(&path, cut_off).unwrap();
+    history.append(&a_run(vec![finished("build", 11)])).unwrap();
+
+    let runs = history.runs().unwrap();
+
+    assert_that(&runs).has_length(2);
+    assert_that(&runs[1].tasks[0].duration).is_equal_to(Some(Duration::from_secs(11)));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_task_that_passes_after_failing_with_the_same_inputs_is_flaky() {
+    let path = a_temp_path("history-flaky");
+    let history = History::new(path.clone());
+    let mut failed_before = a_run(vec![failed("answering")]);
+    failed_before.tasks[0].fingerprint = Some(String::from("inputs"));
+    history.append(&failed_before).unwrap();
+    let fingerprints = HashMap::from([(String::from("answering"), String::from("inputs"))]);
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let store = Box::new(StoreChanges {
+        changes: changes.clone(),
+    });
+    let mut view = HistoryRecorder::new(history, fingerprints, store);
+
+    TASK_RUNNER.run(vec![Box::new(AnsweringTask {})], &mut view);
+    let runs = History::new(path.clone()).runs().unwrap();
+
+    assert_that(&*changes.lock().unwrap()).is_equal_to(vec![
+        TaskChange::TaskStatus(Status::Running),
+        TaskChange::TaskFlaky,
+        TaskChange::TaskStatus(Status::Finished(Some(String::from("42")))),
+    ]);
+    assert_that(&count_outcomes(&runs)).is_equal_to(vec![Flakiness {
+        task: String::from("answering"),
+        passed: 1,
+        failed: 1,
+    }]);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn runs_with_other_inputs_do_not_make_a_task_flaky() {
+    let mut passed = finished("build", 1);
+    passed.fingerprint = Some(String::from("old"));
+    let mut retried = finished("build", 1);
+    retried.fingerprint = Some(String::from("new"));
+    let runs = vec![a_run(vec![failed("build")]), a_run(vec![passed])];
+
+    assert_that(&Flakiness::of("build", Some("old"), &runs).is_flaky()).is_false();
+    retried.attempts = 2;
+    assert_that(&Flakiness::of("build", Some("new"), &[a_run(vec![retried])]).is_flaky()).is_true();
+}
+
+#[test]
+fn tasks_cancelled_by_stopping_a_run_do_not_make_a_task_flaky() {
+    let timed_out = RunRecord {
+        timed_out: true,
+        ..a_run(vec![failed("build")])
+    };
+    let runs = vec![a_run(vec![finished("build", 1)]), timed_out];
+
+    assert_that(&Flakiness::of("build", None, &runs)).is_equal_to(Flakiness {
+        task: String::from("build"),
+        passed: 1,
+        failed: 0,
+    });
+}
+
+fn a_run(tasks: Vec<TaskRecord>) -> RunRecord {
+    RunRecord {
+        started: SystemTime::now(),
+        tasks,
+        timed_out: false,
+        interrupted: false,
+    }
+}
+
+fn finished(name: &str, seconds: u64) -> TaskRecord {
+    TaskRecord {
+        name: String::from(name),
+        status: Status::Finished(None),
+        duration: Some(Duration::from_secs(seconds)),
+        attempts: 1,
+        fingerprint: None,
+    }
+}
+
+fn failed(name: &str) -> TaskRecord {
+    TaskRecord {
+        name: String::from(name),
+        status: Status::Failed(String::from("Broken")),
+        duration: Some(Duration::from_secs(1)),
+        attempts: 1,
+        fingerprint: None,
+    }
+}
+
+struct AnsweringTask {}
+
+impl Task for AnsweringTask {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        Ok(Some(String::from("42")))
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("answering")
+    }
+}
+
+struct StoreChanges {
+    changes: Arc<Mutex<Vec<TaskChange>>>,
+}
+
+impl View for StoreChanges {
+    fn initialize(&mut self, _: Vec<TaskName>) {}
+
+    fn update(&mut self, task_update: TaskUpdate, _: SystemTime) {
+        self.changes.lock().unwrap().push(task_update.change);
+    }
+}
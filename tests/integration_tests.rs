@@ -1,10 +1,15 @@
-use console_runner::{common::*, tasks::*};
-use spectral::prelude::*;
+mod common;
 
-const TASK_RUNNER: TaskRunner = TaskRunner {
-    thread_count: 1,
-    view_update_period: 0,
-};
+use common::*;
+use console_runner::{cache::*, command::*, common::*, selection::*, summary::*, tasks::*};
+use regex::Regex;
+use std::fs;
+use std::process::Command;
+use spectral::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[test]
 fn the_result_of_a_task_is_passed_to_the_view() {
@@ -93,61 +98,860 @@ fn many_tasks_are_run_in_order() {
     ]);
 }
 
-fn a_status(name: &str, status: Status) -> TaskUpdate {
+#[test]
+fn the_summary_contains_the_final_status_of_every_task() {
+    let mut view = StoreToMemory::new();
+    let failing_task = SimpleTask {
+        name: "failing",
+        run_task: || Err(String::from("failure")),
+    };
+    let silent_task = SimpleTask {
+        name: "silent",
+        run_task: || Ok(None),
+    };
+
+    let summary = TASK_RUNNER.run(
+        vec![Box::from(failing_task), Box::from(silent_task)],
+        &mut view,
+    );
+
+    assert_that(&summary.tasks).is_equal_to(vec![
+        (String::from("failing"), Status::Failed(String::from("failure"))),
+        (String::from("silent"), Status::Finished(None)),
+    ]);
+    assert_that(&summary.exit_code(ExitPolicy::Normal)).is_equal_to(EXIT_FAILED);
+    assert_that(&summary.exit_code(ExitPolicy::Lenient)).is_equal_to(EXIT_SUCCESS);
+}
+
+#[test]
+fn a_task_without_an_answer_only_fails_a_strict_run() {
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "silent",
+        run_task: || Ok(None),
+    };
+
+    let summary = TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.exit_code(ExitPolicy::Normal)).is_equal_to(EXIT_SUCCESS);
+    assert_that(&summary.exit_code(ExitPolicy::Strict)).is_equal_to(EXIT_NO_ANSWER);
+}
+
+#[test]
+fn a_run_that_takes_too_long_is_timed_out() {
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "slow",
+        run_task: || {
+            thread::sleep(Duration::from_secs(5));
+            Ok(None)
+        },
+    };
+    let task_runner = TaskRunner {
+        timeout: Some(Duration::from_millis(50)),
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.timed_out).is_true();
+    assert_that(&summary.exit_code(ExitPolicy::Normal)).is_equal_to(EXIT_TIMED_OUT);
+}
+
+#[test]
+fn when_fail_fast_is_set_the_tasks_after_a_failure_are_skipped() {
+    let mut view = StoreToMemory::new();
+    let first_task = SimpleTask {
+        name: "first task",
+        run_task: || Err(String::from("failure")),
+    };
+    let second_task = SimpleTask {
+        name: "second task",
+        run_task: || Ok(None),
+    };
+    let task_runner = TaskRunner {
+        fail_fast: true,
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(
+        vec![Box::from(first_task), Box::from(second_task)],
+        &mut view,
+    );
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("first task", Status::Running),
+        a_status("first task", Status::Failed(String::from("failure"))),
+        a_status("second task", Status::Skipped),
+    ]);
+    assert_that(&summary.exit_code(ExitPolicy::Normal)).is_equal_to(EXIT_FAILED);
+}
+
+#[test]
+fn a_failing_task_is_retried_according_to_its_retry_policy() {
+    let mut view = StoreToMemory::new();
+    let task = FlakyTask {
+        failures_left: AtomicU32::new(1),
+        retry_policy: RetryPolicy {
+            max_attempts: 3,
+            ..RetryPolicy::default()
+        },
+    };
+
+    let summary = TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("flaky", Status::Running),
+        an_attempt("flaky", 2, 3),
+        TaskUpdate {
+            task_name: String::from("flaky"),
+            subtask_path: Vec::new(),
+            change: TaskChange::TaskFlaky,
+        },
+        a_status("flaky", Status::Finished(None)),
+    ]);
+    assert_that(&summary.attempts).is_equal_to(HashMap::from([(String::from("flaky"), 2)]));
+}
+
+#[test]
+fn a_task_is_given_up_on_after_the_last_attempt() {
+    let mut view = StoreToMemory::new();
+    let task = FlakyTask {
+        failures_left: AtomicU32::new(5),
+        retry_policy: RetryPolicy {
+            max_attempts: 2,
+            backoff: Backoff::Exponential(Duration::from_millis(1)),
+            retry_on_panic: false,
+        },
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("flaky", Status::Running),
+        an_attempt("flaky", 2, 2),
+        a_status("flaky", Status::Failed(String::from("not yet"))),
+    ]);
+}
+
+#[test]
+fn tasks_with_a_higher_priority_are_run_first() {
+    let mut view = StoreToMemory::new();
+    let cheap_task = PrioritizedTask {
+        name: "cheap",
+        priority: 1,
+    };
+    let expensive_task = PrioritizedTask {
+        name: "expensive",
+        priority: 10,
+    };
+
+    TASK_RUNNER.run(
+        vec![Box::from(cheap_task), Box::from(expensive_task)],
+        &mut view,
+    );
+
+    assert_that(&view.tasks).is_equal_to(vec![String::from("cheap"), String::from("expensive")]);
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("expensive", Status::Running),
+        a_status("expensive", Status::Finished(None)),
+        a_status("cheap", Status::Running),
+        a_status("cheap", Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn a_task_can_add_more_tasks_to_the_run() {
+    let mut view = StoreToMemory::new();
+    let task = SplittingTask {
+        name: "walk directory",
+        parts: vec!["a.txt", "b.txt"],
+    };
+
+    let summary = TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.added_tasks).is_equal_to(vec![
+        (String::from("a.txt"), Some(String::from("walk directory"))),
+        (String::from("b.txt"), Some(String::from("walk directory"))),
+    ]);
+    assert_that(&summary.tasks).is_equal_to(vec![
+        (String::from("walk directory"), Status::Finished(None)),
+        (String::from("a.txt"), Status::Finished(None)),
+        (String::from("b.txt"), Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn a_subtask_has_its_own_status_and_log() {
+    let mut view = StoreToMemory::new();
+    let task = PhasedTask {
+        phases: vec![("decode", Ok(None))],
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("import video", Status::Running),
+        a_subtask_status("import video", "decode", Status::Running),
+        a_subtask_message("import video", "decode", "working on decode\n"),
+        a_subtask_status("import video", "decode", Status::Finished(None)),
+        a_status("import video", Status::Finished(Some(String::from("done")))),
+    ]);
+}
+
+#[test]
+fn a_failing_subtask_fails_its_task() {
+    let mut view = StoreToMemory::new();
+    let task = PhasedTask {
+        phases: vec![
+            ("decode", Err(String::from("bad codec"))),
+            ("store", Ok(None)),
+        ],
+    };
+
+    let summary = TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("import video"),
+        Status::Failed(String::from("Subtask decode failed")),
+    )]);
+}
+
+#[test]
+fn the_progress_of_a_task_is_passed_to_the_view() {
+    let mut view = StoreToMemory::new();
+    let task = DownloadTask {};
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("download", Status::Running),
+        a_progress("download", Progress::Fraction(0.25)),
+        a_progress(
+            "download",
+            Progress::Count {
+                current: 300,
+                total: 400,
+                unit: String::from("MB"),
+            },
+        ),
+        a_status("download", Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn the_time_of_every_change_is_passed_to_the_view() {
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "my name",
+        run_task: || {
+            thread::sleep(Duration::from_millis(20));
+            Ok(None)
+        },
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.update_times).has_length(2);
+    let running_time = view.update_times[1]
+        .duration_since(view.update_times[0])
+        .unwrap();
+    assert_that(&running_time).is_greater_than_or_equal_to(Duration::from_millis(20));
+}
+
+#[test]
+fn printed_text_is_stamped_no_later_than_the_changes_after_it() {
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "my name",
+        run_task: || {
+            print!("Hello!");
+            Ok(None)
+        },
+    };
+    let task_runner = TaskRunner {
+        view_update_period: 20,
+        ..TASK_RUNNER
+    };
+
+    task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates[1]).is_equal_to(a_message("my name", "Hello!"));
+    assert_that(&view.update_times[1]).is_less_than_or_equal_to(view.update_times[2]);
+}
+
+#[test]
+fn log_records_below_the_log_level_are_dropped() {
+    let mut view = StoreToMemory::new();
+    let task = LeveledTask {};
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("leveled", Status::Running),
+        a_record(
+            "leveled",
+            LogRecord {
+                level: Level::Warn,
+                message: String::from("disk almost full"),
+                fields: vec![(String::from("free"), String::from("2 GB"))],
+            },
+        ),
+        a_status("leveled", Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn what_a_task_writes_to_stderr_is_kept_apart_from_its_log() {
+    let mut view = StoreToMemory::new();
+    let task = DiagnosingTask {};
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("diagnosing", Status::Running),
+        a_message("diagnosing", "result\n"),
+        a_stderr_message("diagnosing", "warning: slow disk\n"),
+        a_status("diagnosing", Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn the_output_of_a_command_is_passed_to_the_view_line_by_line() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "echo downloading; echo size: 42 MB"]);
+    let task = CommandTask::with_answer(
+        "download",
+        command,
+        CommandAnswer::Match(Regex::new(r"size: (\d+ MB)").unwrap()),
+    );
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("download", Status::Running),
+        a_message("download", "downloading\n"),
+        a_message("download", "size: 42 MB\n"),
+        a_status("download", Status::Finished(Some(String::from("42 MB")))),
+    ]);
+}
+
+#[test]
+fn a_command_that_exits_with_an_error_fails_its_task() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "echo no space left >&2; exit 3"]);
+    let task = CommandTask::new("copy", command);
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("copy", Status::Running),
+        a_stderr_message("copy", "no space left\n"),
+        a_status("copy", Status::Failed(String::from("Exited with code 3"))),
+    ]);
+}
+
+#[test]
+fn the_output_of_a_command_is_read_to_the_end_even_if_it_is_not_utf8() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "printf 'caf\\351\\n'; seq 20000; echo done"]);
+    let task = CommandTask::with_answer("reading", command, CommandAnswer::LastLine);
+    let task_runner = TaskRunner {
+        timeout: Some(Duration::from_secs(10)),
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates[1]).is_equal_to(a_message("reading", "caf\u{fffd}\n"));
+    assert_that(&view.task_updates).has_length(20004);
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("reading"),
+        Status::Finished(Some(String::from("done"))),
+    )]);
+}
+
+#[test]
+fn a_command_is_terminated_when_the_run_times_out() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sleep");
+    command.arg("5");
+    let task = CommandTask::new("sleeping", command);
+    let task_runner = TaskRunner {
+        timeout: Some(Duration::from_millis(100)),
+        grace_period: Duration::from_secs(2),
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("sleeping"),
+        Status::Failed(String::from("Killed by SIGTERM")),
+    )]);
+}
+
+#[test]
+fn a_command_that_ignores_sigterm_is_killed_after_the_grace_period() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "trap '' TERM; sleep 5"]);
+    let task = CommandTask::new("stubborn", command);
+    let task_runner = TaskRunner {
+        timeout: Some(Duration::from_millis(100)),
+        grace_period: Duration::from_millis(200),
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("stubborn"),
+        Status::Failed(String::from("Killed by SIGKILL")),
+    )]);
+}
+
+#[test]
+fn a_command_that_times_out_gets_the_grace_period_of_the_runner() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "trap '' TERM; sleep 5"]);
+    let mut task = CommandTask::new("stubborn", command);
+    task.timeout = Some(Duration::from_millis(100));
+    let task_runner = TaskRunner {
+        grace_period: Duration::from_millis(200),
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("stubborn"),
+        Status::Failed(String::from("Timed out after 100ms: Killed by SIGKILL")),
+    )]);
+}
+
+#[test]
+fn a_command_that_closes_its_output_still_times_out() {
+    let mut view = StoreToMemory::new();
+    let mut command = Command::new("sh");
+    command.args(["-c", "exec >/dev/null 2>&1; sleep 3"]);
+    let mut task = CommandTask::new("quiet", command);
+    task.timeout = Some(Duration::from_millis(200));
+    let started = Instant::now();
+
+    let summary = TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("quiet"),
+        Status::Failed(String::from("Timed out after 200ms: Killed by SIGTERM")),
+    )]);
+    assert_that(&started.elapsed()).is_less_than(Duration::from_secs(2));
+}
+
+#[test]
+fn a_task_waits_for_its_dependencies_and_is_skipped_if_one_fails() {
+    let mut view = StoreToMemory::new();
+    let mut install = CommandTask::new("install", Command::new("true"));
+    install.dependencies = vec![String::from("build"), String::from("test")];
+    let mut test = CommandTask::new("test", Command::new("false"));
+    test.dependencies = vec![String::from("build")];
+    let build = CommandTask::new("build", Command::new("true"));
+
+    let summary = TASK_RUNNER.run(
+        vec![Box::from(install), Box::from(test), Box::from(build)],
+        &mut view,
+    );
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("build", Status::Running),
+        a_status("build", Status::Finished(None)),
+        a_status("test", Status::Running),
+        a_status("test", Status::Failed(String::from("Exited with code 1"))),
+        a_status("install", Status::Skipped),
+    ]);
+    assert_that(&summary.tasks[0]).is_equal_to((String::from("install"), Status::Skipped));
+}
+
+#[test]
+fn only_the_selected_tasks_and_their_dependencies_are_run() {
+    let mut view = StoreToMemory::new();
+    let mut lint = CommandTask::new("lint", Command::new("true"));
+    lint.tags = vec![String::from("ci")];
+    let mut test = CommandTask::new("test", Command::new("true"));
+    test.dependencies = vec![String::from("build")];
+    let build = CommandTask::new("build", Command::new("true"));
+    let deploy = CommandTask::new("deploy", Command::new("true"));
+    let task_runner = TaskRunner {
+        selection: Selection {
+            include: vec![Pattern::parse("tag:ci").unwrap(), Pattern::parse("te*").unwrap()],
+            exclude: vec![Pattern::parse("re:^b").unwrap()],
+            show_excluded: true,
+        },
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(
+        vec![
+            Box::from(lint),
+            Box::from(test),
+            Box::from(build),
+            Box::from(deploy),
+        ],
+        &mut view,
+    );
+
+    assert_that(&summary.tasks).is_equal_to(vec![
+        (String::from("lint"), Status::Finished(None)),
+        (String::from("test"), Status::Finished(None)),
+        (String::from("build"), Status::Finished(None)),
+        (String::from("deploy"), Status::Skipped),
+    ]);
+}
+
+#[test]
+fn the_tasks_that_depend_on_a_selected_task_are_run_too() {
+    let mut view = StoreToMemory::new();
+    let mut test = CommandTask::new("test", Command::new("true"));
+    test.dependencies = vec![String::from("build")];
+    let mut install = CommandTask::new("install", Command::new("true"));
+    install.dependencies = vec![String::from("test"), String::from("fetch")];
+    let mut publish = CommandTask::new("publish", Command::new("true"));
+    publish.dependencies = vec![String::from("install")];
+    let build = CommandTask::new("build", Command::new("true"));
+    let fetch = CommandTask::new("fetch", Command::new("true"));
+    let mut docs = CommandTask::new("docs", Command::new("true"));
+    docs.dependencies = vec![String::from("build")];
+    let task_runner = TaskRunner {
+        selection: Selection {
+            include: vec![Pattern::parse("test").unwrap()],
+            exclude: vec![Pattern::parse("publish").unwrap()],
+            show_excluded: true,
+        },
+        ..TASK_RUNNER
+    };
+
+    let summary = task_runner.run(
+        vec![
+            Box::from(test),
+            Box::from(install),
+            Box::from(publish),
+            Box::from(build),
+            Box::from(fetch),
+            Box::from(docs),
+        ],
+        &mut view,
+    );
+
+    assert_that(&summary.tasks).is_equal_to(vec![
+        (String::from("test"), Status::Finished(None)),
+        (String::from("install"), Status::Finished(None)),
+        (String::from("publish"), Status::Skipped),
+        (String::from("build"), Status::Finished(None)),
+        (String::from("fetch"), Status::Finished(None)),
+        (String::from("docs"), Status::Skipped),
+    ]);
+}
+
+#[test]
+fn a_dry_run_plans_the_tasks_in_levels_without_running_them() {
+    let mut view = StoreToMemory::new();
+    let mut install = CommandTask::new("install", Command::new("false"));
+    install.dependencies = vec![String::from("build"), String::from("test")];
+    let mut test = CommandTask::new("test", Command::new("false"));
+    test.dependencies = vec![String::from("build")];
+    let build = CommandTask::new("build", Command::new("false"));
+    let lint = CommandTask::new("lint", Command::new("false"));
+
+    let plan = TASK_RUNNER.plan(
+        vec![
+            Box::from(install),
+            Box::from(test),
+            Box::from(build),
+            Box::from(lint),
+        ],
+        &mut view,
+    );
+
+    assert_that(&view.task_updates).is_empty();
+    assert_that(&plan.to_string()).is_equal_to(String::from(
+        "4 tasks in 3 levels, on 1 threads\n\
+         Level 1: build, lint\n\
+         Level 2: test (after build)\n\
+         Level 3: install (after build, test)",
+    ));
+    assert_that(&plan.to_dot()).is_equal_to(String::from(
+        "digraph tasks {\n    \
+         rankdir = LR;\n    \
+         { rank = same; \"build\"; \"lint\"; }\n    \
+         { rank = same; \"test\"; }\n    \
+         { rank = same; \"install\"; }\n    \
+         \"build\" -> \"test\";\n    \
+         \"build\" -> \"install\";\n    \
+         \"test\" -> \"install\";\n\
+         }\n",
+    ));
+}
+
+#[test]
+fn a_cached_result_is_given_with_its_log_instead_of_running_the_task() {
+    let cache_dir = a_temp_path("cache");
+    let task_runner = TaskRunner {
+        cache_dir: Some(cache_dir.clone()),
+        ..TASK_RUNNER
+    };
+    let mut first_view = StoreToMemory::new();
+    task_runner.run(vec![Box::new(CachedTask {})], &mut first_view);
+    let mut view = StoreToMemory::new();
+
+    let summary = task_runner.run(vec![Box::new(CachedTask {})], &mut view);
+
+    let mut expected_updates: Vec<TaskUpdate> = first_view
+        .task_updates
+        .into_iter()
+        .filter(|update| *update != a_status("cached", Status::Running))
+        .collect();
+    let position = expected_updates.len() - 1;
+    expected_updates.insert(
+        position,
+        TaskUpdate {
+            task_name: String::from("cached"),
+            subtask_path: Vec::new(),
+            change: TaskChange::TaskCached,
+        },
+    );
+    assert_that(&view.task_updates).is_equal_to(expected_updates);
+    assert_that(&summary.tasks[0].1).is_equal_to(Status::Finished(Some(String::from("42"))));
+    assert_that(&CACHED_TASK_RUNS.load(Ordering::Relaxed)).is_equal_to(1);
+    fs::remove_dir_all(cache_dir).unwrap();
+}
+
+#[test]
+fn printed_text_is_stamped_when_it_is_printed() {
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "my name",
+        run_task: || {
+            println!("Hello!");
+            thread::sleep(Duration::from_millis(200));
+            Ok(None)
+        },
+    };
+    let task_runner = TaskRunner {
+        view_update_period: 500,
+        ..TASK_RUNNER
+    };
+
+    task_runner.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates[1]).is_equal_to(a_message("my name", "Hello!\n"));
+    let printed_before_end = view.update_times[2]
+        .duration_since(view.update_times[1])
+        .unwrap();
+    assert_that(&printed_before_end).is_greater_than_or_equal_to(Duration::from_millis(150));
+}
+
+fn a_subtask_status(name: &str, subtask: &str, status: Status) -> TaskUpdate {
     TaskUpdate {
         task_name: String::from(name),
+        subtask_path: vec![String::from(subtask)],
         change: TaskChange::TaskStatus(status),
     }
 }
 
-fn a_message(name: &str, message: &str) -> TaskUpdate {
+fn a_subtask_message(name: &str, subtask: &str, message: &str) -> TaskUpdate {
     TaskUpdate {
         task_name: String::from(name),
+        subtask_path: vec![String::from(subtask)],
         change: TaskChange::TaskMessage(String::from(message)),
     }
 }
 
-struct SimpleTask<'a> {
+fn a_progress(name: &str, progress: Progress) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskProgress(progress),
+    }
+}
+
+fn a_stderr_message(name: &str, message: &str) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskStderr(String::from(message)),
+    }
+}
+
+fn an_attempt(name: &str, number: u32, max_attempts: u32) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: Vec::new(),
+        change: TaskChange::TaskAttempt(Attempt {
+            number,
+            max_attempts,
+        }),
+    }
+}
+
+struct FlakyTask {
+    failures_left: AtomicU32,
+    retry_policy: RetryPolicy,
+}
+
+impl Task for FlakyTask {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        if self.failures_left.load(Ordering::Relaxed) == 0 {
+            return Ok(None);
+        }
+        self.failures_left.fetch_sub(1, Ordering::Relaxed);
+        Err(String::from("not yet"))
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("flaky")
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.clone()
+    }
+}
+
+struct PrioritizedTask<'a> {
     name: &'a str,
-    run_task: fn() -> TaskResult,
+    priority: Priority,
 }
 
-impl<'a> Task for SimpleTask<'a> {
+impl<'a> Task for PrioritizedTask<'a> {
     fn run(&self, _: &dyn Logger) -> TaskResult {
-        let run_task = self.run_task;
-        run_task()
+        Ok(None)
     }
 
     fn name(&self) -> TaskName {
         String::from(self.name)
     }
+
+    fn priority(&self) -> Priority {
+        self.priority
+    }
 }
 
-enum ViewMethod {
-    Initialize,
-    Update,
+struct SplittingTask<'a> {
+    name: &'a str,
+    parts: Vec<&'static str>,
 }
 
-struct StoreToMemory {
-    tasks: Vec<TaskName>,
-    task_updates: Vec<TaskUpdate>,
+impl<'a> Task for SplittingTask<'a> {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        for part in &self.parts {
+            logger.add_child_task(Box::from(PrioritizedTask {
+                name: part,
+                priority: 0,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn name(&self) -> TaskName {
+        String::from(self.name)
+    }
 }
 
-impl StoreToMemory {
-    fn new() -> StoreToMemory {
-        StoreToMemory {
-            tasks: Vec::new(),
-            task_updates: Vec::new(),
+static CACHED_TASK_RUNS: AtomicU32 = AtomicU32::new(0);
+
+struct CachedTask {}
+
+impl Task for CachedTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        CACHED_TASK_RUNS.fetch_add(1, Ordering::Relaxed);
+        println!("computing");
+        let subtask = logger.subtask("check");
+        subtask.log("looks good");
+        subtask.finish(Ok(None));
+        Ok(Some(String::from("42")))
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("cached")
+    }
+
+    fn cache_key(&self) -> Option<CacheKey> {
+        Some(CacheKey {
+            params: vec![(String::from("answer"), String::from("42"))],
+            ..CacheKey::default()
+        })
+    }
+}
+
+struct PhasedTask {
+    phases: Vec<(&'static str, TaskResult)>,
+}
+
+impl Task for PhasedTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        for (phase, result) in &self.phases {
+            let subtask = logger.subtask(phase);
+            subtask.log(&format!("working on {}", phase));
+            subtask.finish(result.clone());
         }
+        Ok(Some(String::from("done")))
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("import video")
+    }
+}
+
+struct DownloadTask {}
+
+impl Task for DownloadTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        logger.progress(Progress::Fraction(0.25));
+        logger.progress(Progress::Count {
+            current: 300,
+            total: 400,
+            unit: String::from("MB"),
+        });
+        Ok(None)
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("download")
+    }
+}
+
+struct LeveledTask {}
+
+impl Task for LeveledTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        logger.debug("checking disk");
+        logger.record(Level::Warn, "disk almost full", &[("free", "2 GB")]);
+        Ok(None)
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("leveled")
     }
 }
 
-impl View for StoreToMemory {
-    fn initialize(&mut self, tasks: Vec<TaskName>) {
-        self.tasks = tasks;
+struct DiagnosingTask {}
+
+impl Task for DiagnosingTask {
+    fn run(&self, logger: &dyn Logger) -> TaskResult {
+        logger.log("result");
+        logger.log_stderr("warning: slow disk");
+        Ok(None)
     }
 
-    fn update(&mut self, task_update: TaskUpdate) {
-        self.task_updates.push(task_update);
+    fn name(&self) -> TaskName {
+        String::from("diagnosing")
     }
 }
+
+enum ViewMethod {
+    Initialize,
+    Update,
+}
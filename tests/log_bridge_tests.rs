@@ -0,0 +1,70 @@
+mod common;
+
+use common::*;
+use console_runner::{common::*, log_bridge::LogBridge};
+use log::LevelFilter;
+use spectral::prelude::*;
+use std::sync::{Mutex, MutexGuard, Once};
+
+// The logger of the log crate can only be set once per process.
+static INSTALL: Once = Once::new();
+// The records logged outside of the tasks are shared by all runs, so the
+// tests take turns.
+static RUN: Mutex<()> = Mutex::new(());
+
+fn install_log_bridge() -> MutexGuard<'static, ()> {
+    INSTALL.call_once(|| LogBridge::install(LevelFilter::Trace).unwrap());
+    RUN.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[test]
+fn what_a_task_logs_through_the_log_crate_is_passed_to_the_view() {
+    let _run = install_log_bridge();
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "logging",
+        run_task: || {
+            log::debug!("hidden below the log level");
+            log::warn!(free = "2 GB"; "disk almost full");
+            Ok(None)
+        },
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("logging", Status::Running),
+        a_record(
+            "logging",
+            LogRecord {
+                level: Level::Warn,
+                message: String::from("disk almost full"),
+                fields: vec![(String::from("free"), String::from("2 GB"))],
+            },
+        ),
+        a_status("logging", Status::Finished(None)),
+    ]);
+}
+
+#[test]
+fn what_is_logged_outside_of_the_tasks_is_passed_to_the_view() {
+    let _run = install_log_bridge();
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "spawning",
+        run_task: || {
+            std::thread::spawn(|| log::info!("from a thread of its own"))
+                .join()
+                .unwrap();
+            Ok(None)
+        },
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.runner_records).contains(LogRecord {
+        level: Level::Info,
+        message: String::from("from a thread of its own"),
+        fields: Vec::new(),
+    });
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use console_runner::{common::*, recording::*, tasks::*};
+use spectral::prelude::*;
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[test]
+fn a_recorded_run_is_replayed_into_another_view() {
+    let path = a_temp_path("replayed.rec");
+    let mut recorder = Recorder::create(&path, Box::new(StoreToMemory::new())).unwrap();
+    let summary = TASK_RUNNER.run(vec![Box::new(PrintingTask {})], &mut recorder);
+    let mut view = StoreToMemory::new();
+
+    let replayed_summary = replay(load(&path).unwrap(), f64::INFINITY, &mut view);
+
+    assert_that(&view.tasks).is_equal_to(vec![String::from("printing")]);
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_status("printing", Status::Running),
+        a_message("printing", "Hello\n"),
+        a_status("printing", Status::Finished(Some(String::from("World")))),
+    ]);
+    assert_that(&replayed_summary).is_equal_to(Some(summary));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_recording_that_was_cut_off_is_loaded_up_to_the_last_whole_event() {
+    let path = a_temp_path("cut-off.rec");
+    let mut recorder = Recorder::create(&path, Box::new(StoreToMemory::new())).unwrap();
+    TASK_RUNNER.run(vec![Box::new(PrintingTask {})], &mut recorder);
+    let mut events = load(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+
+    let loaded = load(&path).unwrap();
+
+    events.pop();
+    assert_that(&loaded).is_equal_to(events);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_replay_waits_the_recorded_time_divided_by_the_speed() {
+    let events = vec![
+        (
+            Duration::ZERO,
+            RecordedEvent::Initialize(vec![String::from("slow")]),
+        ),
+        (
+            Duration::from_secs(1),
+            RecordedEvent::Update(a_status("slow", Status::Running)),
+        ),
+    ];
+    let mut view = StoreToMemory::new();
+    let started = Instant::now();
+
+    replay(events, 10.0, &mut view);
+
+    assert_that(&started.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(100));
+    assert_that(&started.elapsed()).is_less_than(Duration::from_secs(1));
+    assert_that(&view.task_updates).is_equal_to(vec![a_status("slow", Status::Running)]);
+}
+
+#[test]
+fn a_file_that_is_not_a_recording_is_not_replayed() {
+    let path = a_temp_path("invalid.rec");
+    fs::write(&path, "[[task]]\n").unwrap();
+
+    let error = load(&path).err().unwrap();
+
+    assert_that(&error.to_string()).is_equal_to(String::from("not a recording"));
+    fs::remove_file(path).unwrap();
+}
+
+struct PrintingTask {}
+
+impl Task for PrintingTask {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        println!("Hello");
+        Ok(Some(String::from("World")))
+    }
+
+    fn name(&self) -> TaskName {
+        String::from("printing")
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::*;
+use serde_json::Value;
+use spectral::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const TASK_FILE: &str = r#"
+[[task]]
+name = "print <all>"
+command = "sh"
+args = ["-c", '''printf 'a < b & \033[31mred\033[0m\n'; exit 3''']
+
+[[task]]
+name = "after"
+command = "true"
+depends_on = ["print <all>"]
+"#;
+
+#[test]
+fn the_junit_report_has_escaped_output_failures_and_skipped_tasks() {
+    let directory = a_temp_path("junit-report");
+
+    let output = run_with_output(&directory, &["-o", "junit"]);
+
+    assert_that(&output).contains(r#"<testcase name="print &lt;all&gt;""#);
+    assert_that(&output).contains(r#"<failure message="Exited with code 3"/>"#);
+    assert_that(&output).contains("a &lt; b &amp; [31mred[0m\n");
+    assert_that(&output.contains('\u{1b}')).is_false();
+    assert_that(&output).contains("<testcase name=\"after\" time=\"0.000\">\n    <skipped/>");
+    assert_that(&output.trim_end().ends_with("</testsuite>")).is_true();
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn the_json_report_has_an_object_on_every_line() {
+    let directory = a_temp_path("json-report");
+
+    let output = run_with_output(&directory, &["-o", "json"]);
+
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_that(&lines.iter().all(Value::is_object)).is_true();
+    assert_that(&lines[0]).is_equal_to(serde_json::json!({ "tasks": ["print <all>", "after"] }));
+    assert_that(&lines.last().unwrap()["summary"]["attempts"].is_object()).is_true();
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn the_reports_are_complete_when_no_task_is_selected() {
+    let directory = a_temp_path("empty-report");
+
+    let junit = run_with_output(&directory, &["-o", "junit", "-f", "nothing"]);
+    let json = run_with_output(&directory, &["-o", "json", "-f", "nothing"]);
+
+    assert_that(&junit).contains(r#"<testsuite name="console-runner" tests="0""#);
+    assert_that(&junit.trim_end().ends_with("</testsuite>")).is_true();
+    let last_line: Value = serde_json::from_str(json.lines().last().unwrap()).unwrap();
+    assert_that(&last_line["summary"]["tasks"]).is_equal_to(serde_json::json!([]));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+fn run_with_output(directory: &Path, args: &[&str]) -> String {
+    fs::create_dir_all(directory).unwrap();
+    fs::write(directory.join("tasks.toml"), TASK_FILE).unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_console-runner"))
+        .args(args)
+        .args(["--no-cache", "--no-history", "tasks.toml"])
+        .current_dir(directory)
+        .output()
+        .unwrap();
+    String::from_utf8(run.stdout).unwrap()
+}
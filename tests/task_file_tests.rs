@@ -0,0 +1,96 @@
+mod common;
+
+use common::*;
+use console_runner::{common::*, task_file};
+use spectral::prelude::*;
+use std::time::{Duration, Instant};
+
+#[test]
+fn the_tasks_of_a_task_file_are_loaded_in_order() {
+    let tasks = task_file::parse(
+        r#"
+[[task]]
+name = "build"
+command = "cargo"
+args = ["build"]
+
+[[task]]
+name = "test"
+command = "cargo"
+args = ["test"]
+env = { RUST_BACKTRACE = "1" }
+depends_on = ["build"]
+timeout = "10m"
+retries = 2
+tags = ["ci"]
+"#,
+        "tasks.toml",
+    )
+    .unwrap();
+
+    let names: Vec<TaskName> = tasks.iter().map(|task| task.name()).collect();
+    assert_that(&names).is_equal_to(vec![String::from("build"), String::from("test")]);
+    assert_that(&tasks[1].dependencies()).is_equal_to(vec![String::from("build")]);
+    assert_that(&tasks[1].retry_policy().max_attempts).is_equal_to(3);
+}
+
+#[test]
+fn an_error_in_a_task_file_points_to_its_line_and_field() {
+    let error_of = |content: &str| task_file::parse(content, "tasks.toml").err().unwrap();
+
+    assert_that(&error_of(
+        "[[task]]\nname = \"test\"\ncommand = \"cargo\"\ndepends_on = [\"build\"]\n",
+    ))
+    .is_equal_to(String::from(
+        "tasks.toml:4: task `test`, field `depends_on`: there is no task named `build`",
+    ));
+    assert_that(&error_of(
+        "[[task]]\nname = \"test\"\ncommand = \"cargo\"\ntimeout = \"soon\"\n",
+    ))
+    .is_equal_to(String::from(
+        "tasks.toml:4: task `test`, field `timeout`: Invalid time: soon",
+    ));
+    assert_that(&error_of(
+        "[[task]]\nname = \"a\"\ncommand = \"true\"\ndepends_on = [\"b\"]\n\
+         [[task]]\nname = \"b\"\ncommand = \"true\"\ndepends_on = [\"a\"]\n",
+    ))
+    .is_equal_to(String::from(
+        "tasks.toml:4: task `a`, field `depends_on`: `b` depends on this task",
+    ));
+    assert_that(&error_of(
+        "[[task]]\nname = \"test\"\ncommand = \"cargo\"\nretries = 4294967295\n",
+    ))
+    .is_equal_to(String::from(
+        "tasks.toml:4: task `test`, field `retries`: at most 4294967294 retries are possible",
+    ));
+    let missing_field = error_of("[[task]]\nname = \"test\"\nargs = [\"test\"]\n");
+    assert_that(&missing_field).contains("line 1");
+    assert_that(&missing_field).contains("missing field `command`");
+    let unknown_field = error_of("[[task]]\nname = \"test\"\ncommand = \"cargo\"\nretry = 2\n");
+    assert_that(&unknown_field).contains("line 4");
+    assert_that(&unknown_field).contains("unknown field `retry`");
+}
+
+#[test]
+fn a_command_that_closes_its_output_gets_the_timeout_of_its_task() {
+    let tasks = task_file::parse(
+        r#"
+[[task]]
+name = "quiet"
+command = "sh"
+args = ["-c", "exec >/dev/null 2>&1; sleep 3"]
+timeout = "200ms"
+"#,
+        "tasks.toml",
+    )
+    .unwrap();
+    let started = Instant::now();
+
+    let summary = TASK_RUNNER.run(tasks, &mut NoView {});
+
+    assert_that(&summary.tasks).is_equal_to(vec![(
+        String::from("quiet"),
+        Status::Failed(String::from("Timed out after 200ms: Killed by SIGTERM")),
+    )]);
+    assert_that(&started.elapsed()).is_less_than(Duration::from_secs(2));
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "tracing")]
+mod common;
+
+use common::*;
+use console_runner::{common::*, tracing_layer::TaskLayer};
+use spectral::prelude::*;
+use std::sync::Once;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Registry;
+
+// The tasks run on threads of their own, so the layer has to be set as the
+// global subscriber, which can only be done once per process.
+static INSTALL: Once = Once::new();
+
+fn install_task_layer() {
+    INSTALL.call_once(|| {
+        tracing::subscriber::set_global_default(Registry::default().with(TaskLayer)).unwrap()
+    });
+}
+
+#[test]
+fn a_span_opened_by_a_task_is_shown_as_a_subtask() {
+    install_task_layer();
+    let mut view = StoreToMemory::new();
+    let task = SimpleTask {
+        name: "traced",
+        run_task: || {
+            tracing::info!("starting");
+            let span = tracing::info_span!("download");
+            let _entered = span.enter();
+            tracing::warn!(free = "2 GB", "disk almost full");
+            Ok(None)
+        },
+    };
+
+    TASK_RUNNER.run(vec![Box::from(task)], &mut view);
+
+    assert_that(&view.task_updates).is_equal_to(vec![
+        a_subtask_update("traced", &[], TaskChange::TaskStatus(Status::Running)),
+        a_subtask_update(
+            "traced",
+            &[],
+            TaskChange::TaskRecord(LogRecord {
+                level: Level::Info,
+                message: String::from("starting"),
+                fields: Vec::new(),
+            }),
+        ),
+        a_subtask_update(
+            "traced",
+            &["download"],
+            TaskChange::TaskStatus(Status::Running),
+        ),
+        a_subtask_update(
+            "traced",
+            &["download"],
+            TaskChange::TaskRecord(LogRecord {
+                level: Level::Warn,
+                message: String::from("disk almost full"),
+                fields: vec![(String::from("free"), String::from("2 GB"))],
+            }),
+        ),
+        a_subtask_update(
+            "traced",
+            &["download"],
+            TaskChange::TaskStatus(Status::Finished(None)),
+        ),
+        a_subtask_update("traced", &[], TaskChange::TaskStatus(Status::Finished(None))),
+    ]);
+}
+
+fn a_subtask_update(name: &str, subtask_path: &[&str], change: TaskChange) -> TaskUpdate {
+    TaskUpdate {
+        task_name: String::from(name),
+        subtask_path: subtask_path.iter().map(|name| String::from(*name)).collect(),
+        change,
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::*;
+use console_runner::{common::*, tasks::*, watch::*};
+use spectral::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[test]
+fn the_tasks_with_changed_inputs_and_the_tasks_after_them_are_run_again() {
+    let directory = a_temp_path("changed");
+    fs::create_dir_all(directory.join("src")).unwrap();
+    fs::create_dir_all(directory.join("docs")).unwrap();
+    let tasks: Vec<Box<dyn Task>> = vec![
+        Box::new(WatchingTask {
+            name: "build",
+            inputs: vec![directory.join("src")],
+            dependencies: vec![],
+        }),
+        Box::new(WatchingTask {
+            name: "docs",
+            inputs: vec![directory.join("docs")],
+            dependencies: vec![],
+        }),
+        Box::new(WatchingTask {
+            name: "test",
+            inputs: vec![],
+            dependencies: vec![String::from("build")],
+        }),
+    ];
+    let watcher = InputWatcher::new(&tasks, Duration::from_millis(50)).unwrap();
+
+    assert_that(&watcher.changes(Duration::from_millis(100))).is_equal_to(Changes::default());
+    fs::write(directory.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+    assert_that(&watcher.changes(Duration::from_secs(5)).tasks)
+        .is_equal_to(vec![String::from("build"), String::from("test")]);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn an_input_that_does_not_exist_cannot_be_watched() {
+    let directory = a_temp_path("missing");
+    let tasks: Vec<Box<dyn Task>> = vec![Box::new(WatchingTask {
+        name: "build",
+        inputs: vec![directory.join("src")],
+        dependencies: vec![],
+    })];
+
+    let error = InputWatcher::new(&tasks, Duration::from_millis(50))
+        .err()
+        .unwrap();
+
+    assert_that(&error).starts_with("Could not watch");
+}
+
+#[test]
+fn a_task_file_that_is_replaced_is_a_change() {
+    let directory = a_temp_path("task-file");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("tasks.toml"), "").unwrap();
+    let mut watcher = InputWatcher::new(&[], Duration::from_millis(50)).unwrap();
+    watcher.watch_task_file(&directory.join("tasks.toml")).unwrap();
+
+    fs::write(directory.join("notes.txt"), "").unwrap();
+    assert_that(&watcher.changes(Duration::from_millis(200))).is_equal_to(Changes::default());
+    fs::write(directory.join("tasks.toml.new"), "[[task]]\n").unwrap();
+    fs::rename(directory.join("tasks.toml.new"), directory.join("tasks.toml")).unwrap();
+
+    assert_that(&watcher.changes(Duration::from_secs(5)).task_file).is_true();
+    fs::remove_dir_all(directory).unwrap();
+}
+
+struct WatchingTask<'a> {
+    name: &'a str,
+    inputs: Vec<PathBuf>,
+    dependencies: Vec<TaskName>,
+}
+
+impl<'a> Task for WatchingTask<'a> {
+    fn run(&self, _: &dyn Logger) -> TaskResult {
+        Ok(None)
+    }
+
+    fn name(&self) -> TaskName {
+        String::from(self.name)
+    }
+
+    fn dependencies(&self) -> Vec<TaskName> {
+        self.dependencies.clone()
+    }
+
+    fn inputs(&self) -> Vec<PathBuf> {
+        self.inputs.clone()
+    }
+}